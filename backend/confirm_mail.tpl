@@ -0,0 +1,19 @@
+Hallo {{name}},
+
+vielen Dank für die Meldung für {{club}} zum {{tournament.name}} am {{tournament.date}}.
+
+Bitte bestätige die Anmeldung innerhalb von {{valid_hours}} Stunden über folgenden Link:
+{{confirmation_link}}
+
+Erst nach der Bestätigung ist die Anmeldung verbindlich und du erhältst eine Bestätigungsmail mit allen Details.
+
+Folgende Schützen werden eingetragen:
+{{#each archers}}
+- {{this}}
+{{/each}}
+
+Falls du dich nicht angemeldet hast, kannst du diese Mail einfach ignorieren.
+
+Viele Grüße und Alle ins Gold
+Tobias Edlböck
+PSV München
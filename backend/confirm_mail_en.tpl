@@ -0,0 +1,19 @@
+Hello {{name}},
+
+Thanks for your registration of {{club}} at {{tournament.name}} on {{tournament.date}}.
+
+Please confirm your registration within {{valid_hours}} hours using the following link:
+{{confirmation_link}}
+
+Your registration only becomes binding after the confirmation. You will then receive a confirmation mail with all details.
+
+Following archers will be entered:
+{{#each archers}}
+- {{this}}
+{{/each}}
+
+If you didn't register, you can simply ignore this mail.
+
+Kind Regards
+Tobias Edlböck
+PSV München
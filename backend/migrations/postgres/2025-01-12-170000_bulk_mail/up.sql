@@ -0,0 +1,14 @@
+-- Your SQL goes here
+ALTER TABLE archer_additions
+ADD "paid" INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE "mail_queue" (
+	"id"	SERIAL PRIMARY KEY,
+	"recipient"	TEXT NOT NULL,
+	"subject"	TEXT NOT NULL,
+	"body"	TEXT NOT NULL,
+	"created_at"	TEXT NOT NULL,
+	"sent_at"	TEXT,
+	"attempts"	INTEGER NOT NULL DEFAULT 0,
+	"last_error"	TEXT
+);
@@ -0,0 +1,27 @@
+-- Your SQL goes here
+CREATE TABLE "tournaments" (
+	"id"	SERIAL PRIMARY KEY,
+	"slug"	TEXT NOT NULL UNIQUE,
+	"name"	TEXT NOT NULL,
+	"date"	TEXT NOT NULL,
+	"venue"	TEXT NOT NULL DEFAULT '',
+	"season_start"	TEXT NOT NULL,
+	"kind"	TEXT NOT NULL DEFAULT 'Indoor',
+	"sessions"	TEXT NOT NULL DEFAULT '[]',
+	"classes"	TEXT NOT NULL DEFAULT '[]',
+	"prices"	TEXT NOT NULL DEFAULT '{}',
+	"registration_opens"	TEXT,
+	"registration_closes"	TEXT
+);
+
+-- Registrations so far belong to the indoor tournament 2025
+INSERT INTO "tournaments" ("id", "slug", "name", "date", "season_start", "sessions")
+VALUES (1, 'indoor25', 'PSV Indoor Turnier 2025', '2025-02-23', '2025-01-01',
+	'[{"en":"Morning","de":"Vormittag"},{"en":"Afternoon","de":"Nachmittag"}]');
+SELECT setval(pg_get_serial_sequence('tournaments', 'id'), 1);
+
+ALTER TABLE archer_additions
+ADD "tournament_id" INTEGER NOT NULL DEFAULT 1;
+
+ALTER TABLE pending_registrations
+ADD "tournament_id" INTEGER NOT NULL DEFAULT 1;
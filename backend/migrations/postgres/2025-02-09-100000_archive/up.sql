@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE tournaments
+ADD "archived_at" TEXT;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE "teams" (
+	"id"	SERIAL PRIMARY KEY,
+	"tournament_id"	INTEGER NOT NULL,
+	"name"	TEXT NOT NULL,
+	"class"	TEXT NOT NULL,
+	"email"	TEXT NOT NULL
+);
+
+ALTER TABLE archer_additions
+ADD "team_id" INTEGER;
@@ -0,0 +1,84 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE "archers" RENAME TO "normalized_archers";
+ALTER TABLE "teams" RENAME TO "normalized_teams";
+
+CREATE TABLE "archers" (
+	"bib"	SERIAL PRIMARY KEY,
+	"session"	INTEGER NOT NULL,
+	"division"	TEXT NOT NULL,
+	"class"	TEXT NOT NULL,
+	"target"	TEXT NOT NULL,
+	"individual qualification"	INTEGER NOT NULL,
+	"team qualification"	INTEGER NOT NULL,
+	"individual final"	INTEGER NOT NULL,
+	"team final"	INTEGER NOT NULL,
+	"mixed team final"	INTEGER NOT NULL,
+	"last name"	TEXT NOT NULL,
+	"first name"	TEXT NOT NULL,
+	"gender"	INTEGER,
+	"country code"	TEXT NOT NULL,
+	"country name"	TEXT NOT NULL,
+	"date of birth"	TEXT NOT NULL,
+	"subclass"	TEXT,
+	"country code 2"	TEXT,
+	"country name 2"	TEXT,
+	"country code 3"	TEXT,
+	"country name 3"	TEXT
+);
+CREATE TABLE "archer_additions" (
+	"bib"	INTEGER PRIMARY KEY,
+	"email"	TEXT,
+	"comment"	TEXT,
+	"target face"	TEXT,
+	"paid"	INTEGER NOT NULL DEFAULT 0,
+	"tournament_id"	INTEGER NOT NULL DEFAULT 1,
+	"team_id"	INTEGER
+);
+CREATE TABLE "teams" (
+	"id"	SERIAL PRIMARY KEY,
+	"tournament_id"	INTEGER NOT NULL,
+	"name"	TEXT NOT NULL,
+	"class"	TEXT NOT NULL,
+	"email"	TEXT NOT NULL
+);
+
+INSERT INTO "archers"
+SELECT "bib", "session" + 1,
+	CASE "bow_type"
+		WHEN 'Compound' THEN 'C'
+		WHEN 'Barebow' THEN 'B'
+		WHEN 'Longbow' THEN 'L'
+		WHEN 'Traditional' THEN 'T'
+		WHEN 'Instinctive' THEN 'I'
+		ELSE 'R'
+	END,
+	"class", '', 1, CAST("team_id" IS NOT NULL AS INTEGER),
+	CAST("individual_final" AS INTEGER), CAST("team_final" AS INTEGER),
+	CAST("mixed_team_final" AS INTEGER),
+	"last_name", "first_name",
+	CASE "gender" WHEN 'Male' THEN 0 WHEN 'Female' THEN 1 END,
+	COALESCE("club_code", ''), "club_name", CAST("date_of_birth" AS TEXT),
+	NULL, '', '', '', ''
+FROM "normalized_archers";
+SELECT setval(pg_get_serial_sequence('archers', 'bib'), COALESCE(MAX("bib"), 0) + 1, false)
+FROM "archers";
+
+INSERT INTO "archer_additions"
+SELECT "normalized_archers"."bib", "registrations"."email", "registrations"."comment",
+	"normalized_archers"."target_face", CAST("normalized_archers"."paid" AS INTEGER),
+	"registrations"."tournament_id", "normalized_archers"."team_id"
+FROM "normalized_archers"
+INNER JOIN "registrations" ON "registrations"."id" = "normalized_archers"."registration_id";
+
+INSERT INTO "teams"
+SELECT "normalized_teams"."id", "registrations"."tournament_id",
+	"normalized_teams"."name", "normalized_teams"."class",
+	COALESCE("registrations"."email", '')
+FROM "normalized_teams"
+INNER JOIN "registrations" ON "registrations"."id" = "normalized_teams"."registration_id";
+SELECT setval(pg_get_serial_sequence('teams', 'id'), COALESCE(MAX("id"), 0) + 1, false)
+FROM "teams";
+
+DROP TABLE "normalized_archers";
+DROP TABLE "normalized_teams";
+DROP TABLE "registrations";
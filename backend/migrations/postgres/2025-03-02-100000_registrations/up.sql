@@ -0,0 +1,105 @@
+-- Your SQL goes here
+-- Replaces the Ianseo shaped archers table. Ianseo gets its layout from the export.
+CREATE TABLE "registrations" (
+	"id"	SERIAL PRIMARY KEY,
+	"tournament_id"	INTEGER NOT NULL REFERENCES "tournaments" ("id"),
+	"name"	TEXT NOT NULL,
+	-- NULL once anonymized
+	"email"	TEXT,
+	"club"	TEXT NOT NULL,
+	"comment"	TEXT NOT NULL,
+	"locale"	TEXT NOT NULL DEFAULT 'De',
+	"created_at"	TIMESTAMP NOT NULL
+);
+
+-- Registrations before only exist as archers sharing a mail address
+INSERT INTO "registrations" ("tournament_id", "name", "email", "club", "comment", "created_at")
+SELECT "archer_additions"."tournament_id", '', "archer_additions"."email",
+	MIN("archers"."country name"), COALESCE(MAX("archer_additions"."comment"), ''),
+	CURRENT_TIMESTAMP
+FROM "archer_additions"
+INNER JOIN "archers" ON "archers"."bib" = "archer_additions"."bib"
+GROUP BY "archer_additions"."tournament_id", "archer_additions"."email";
+
+ALTER TABLE "archers" RENAME TO "legacy_archers";
+ALTER TABLE "teams" RENAME TO "legacy_teams";
+
+CREATE TABLE "teams" (
+	"id"	SERIAL PRIMARY KEY,
+	"registration_id"	INTEGER NOT NULL REFERENCES "registrations" ("id"),
+	"name"	TEXT NOT NULL,
+	"class"	TEXT NOT NULL
+);
+
+-- Enums are stored with the names of their variants
+CREATE TABLE "archers" (
+	"bib"	SERIAL PRIMARY KEY,
+	"registration_id"	INTEGER NOT NULL REFERENCES "registrations" ("id"),
+	"team_id"	INTEGER REFERENCES "teams" ("id"),
+	-- Index into the sessions of the tournament, followed by the waiting lists
+	"session"	INTEGER NOT NULL,
+	"first_name"	TEXT NOT NULL,
+	"last_name"	TEXT NOT NULL,
+	"date_of_birth"	DATE NOT NULL,
+	"gender"	TEXT,
+	"bow_type"	TEXT NOT NULL,
+	"class"	TEXT NOT NULL,
+	"target_face"	TEXT NOT NULL,
+	-- NULL as long as the club isn't found in the club registry
+	"club_code"	TEXT REFERENCES "clubs" ("code"),
+	"club_name"	TEXT NOT NULL,
+	"paid"	BOOLEAN NOT NULL DEFAULT FALSE,
+	"individual_final"	BOOLEAN NOT NULL DEFAULT FALSE,
+	"team_final"	BOOLEAN NOT NULL DEFAULT FALSE,
+	"mixed_team_final"	BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Teams are inserted first, so the archers can reference them
+INSERT INTO "teams"
+SELECT "legacy_teams"."id",
+	(SELECT "registrations"."id"
+		FROM "archer_additions"
+		INNER JOIN "registrations"
+			ON "registrations"."tournament_id" = "archer_additions"."tournament_id"
+			AND "registrations"."email" IS NOT DISTINCT FROM "archer_additions"."email"
+		WHERE "archer_additions"."team_id" = "legacy_teams"."id"
+		LIMIT 1),
+	"legacy_teams"."name", "legacy_teams"."class"
+FROM "legacy_teams"
+WHERE EXISTS (SELECT 1 FROM "archer_additions" WHERE "team_id" = "legacy_teams"."id");
+SELECT setval(pg_get_serial_sequence('teams', 'id'), COALESCE(MAX("id"), 0) + 1, false)
+FROM "teams";
+
+INSERT INTO "archers"
+SELECT "legacy_archers"."bib", "registrations"."id", "teams"."id",
+	"legacy_archers"."session" - 1,
+	"legacy_archers"."first name", "legacy_archers"."last name",
+	CAST("legacy_archers"."date of birth" AS DATE),
+	CASE "legacy_archers"."gender" WHEN 0 THEN 'Male' WHEN 1 THEN 'Female' END,
+	CASE "legacy_archers"."division"
+		WHEN 'C' THEN 'Compound'
+		WHEN 'B' THEN 'Barebow'
+		WHEN 'L' THEN 'Longbow'
+		WHEN 'T' THEN 'Traditional'
+		WHEN 'I' THEN 'Instinctive'
+		ELSE 'Recurve'
+	END,
+	"legacy_archers"."class", COALESCE("archer_additions"."target face", ''),
+	(SELECT "code" FROM "clubs" WHERE "code" = "legacy_archers"."country code"),
+	"legacy_archers"."country name",
+	"archer_additions"."paid" <> 0,
+	"legacy_archers"."individual final" <> 0,
+	"legacy_archers"."team final" <> 0,
+	"legacy_archers"."mixed team final" <> 0
+FROM "legacy_archers"
+INNER JOIN "archer_additions" ON "archer_additions"."bib" = "legacy_archers"."bib"
+INNER JOIN "registrations"
+	ON "registrations"."tournament_id" = "archer_additions"."tournament_id"
+	AND "registrations"."email" IS NOT DISTINCT FROM "archer_additions"."email"
+LEFT JOIN "teams" ON "teams"."id" = "archer_additions"."team_id";
+SELECT setval(pg_get_serial_sequence('archers', 'bib'), COALESCE(MAX("bib"), 0) + 1, false)
+FROM "archers";
+
+DROP TABLE "archer_additions";
+DROP TABLE "legacy_archers";
+DROP TABLE "legacy_teams";
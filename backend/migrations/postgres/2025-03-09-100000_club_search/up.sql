@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE clubs
+ADD "search" TEXT NOT NULL DEFAULT '';
+-- Umlauts are transcribed once the clubs are imported again
+UPDATE clubs SET "search" = lower("code" || ' ' || "name");
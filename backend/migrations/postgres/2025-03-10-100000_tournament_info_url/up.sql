@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE tournaments
+ADD "info_url" TEXT NOT NULL DEFAULT '';
+
+UPDATE tournaments SET "info_url" = 'https://bogen-psv.de/indoor.html' WHERE "slug" = 'indoor25';
@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE tournaments
+	ALTER COLUMN "date" TYPE TEXT USING to_char("date", 'YYYY-MM-DD'),
+	ALTER COLUMN "season_start" TYPE TEXT USING to_char("season_start", 'YYYY-MM-DD'),
+	ALTER COLUMN "registration_opens" TYPE TEXT
+		USING to_char("registration_opens", 'YYYY-MM-DD HH24:MI:SS'),
+	ALTER COLUMN "registration_closes" TYPE TEXT
+		USING to_char("registration_closes", 'YYYY-MM-DD HH24:MI:SS'),
+	ALTER COLUMN "archived_at" TYPE TEXT
+		USING to_char("archived_at", 'YYYY-MM-DD HH24:MI:SS');
+
+ALTER TABLE mail_queue
+	ALTER COLUMN "created_at" TYPE TEXT USING to_char("created_at", 'YYYY-MM-DD HH24:MI:SS'),
+	ALTER COLUMN "sent_at" TYPE TEXT USING to_char("sent_at", 'YYYY-MM-DD HH24:MI:SS');
+
+ALTER TABLE pending_registrations
+	ALTER COLUMN "created_at" TYPE TEXT USING to_char("created_at", 'YYYY-MM-DD HH24:MI:SS');
+
+ALTER TABLE prefill_tokens
+	ALTER COLUMN "created_at" TYPE TEXT USING to_char("created_at", 'YYYY-MM-DD HH24:MI:SS');
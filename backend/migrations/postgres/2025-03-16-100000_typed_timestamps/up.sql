@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TABLE tournaments
+	ALTER COLUMN "date" TYPE DATE USING CAST("date" AS DATE),
+	ALTER COLUMN "season_start" TYPE DATE USING CAST("season_start" AS DATE),
+	ALTER COLUMN "registration_opens" TYPE TIMESTAMP
+		USING CAST("registration_opens" AS TIMESTAMP),
+	ALTER COLUMN "registration_closes" TYPE TIMESTAMP
+		USING CAST("registration_closes" AS TIMESTAMP),
+	ALTER COLUMN "archived_at" TYPE TIMESTAMP USING CAST("archived_at" AS TIMESTAMP);
+
+ALTER TABLE mail_queue
+	ALTER COLUMN "created_at" TYPE TIMESTAMP USING CAST("created_at" AS TIMESTAMP),
+	ALTER COLUMN "sent_at" TYPE TIMESTAMP USING CAST("sent_at" AS TIMESTAMP);
+
+ALTER TABLE pending_registrations
+	ALTER COLUMN "created_at" TYPE TIMESTAMP USING CAST("created_at" AS TIMESTAMP);
+
+ALTER TABLE prefill_tokens
+	ALTER COLUMN "created_at" TYPE TIMESTAMP USING CAST("created_at" AS TIMESTAMP);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE "mail_queue";
+
+ALTER TABLE archer_additions
+DROP COLUMN "paid";
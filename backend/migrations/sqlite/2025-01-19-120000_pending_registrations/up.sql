@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE "pending_registrations" (
+	"token"	TEXT NOT NULL,
+	"payload"	TEXT NOT NULL,
+	"created_at"	TEXT NOT NULL,
+	PRIMARY KEY("token")
+);
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE "clubs" (
+	"code"	TEXT NOT NULL,
+	"name"	TEXT NOT NULL,
+	PRIMARY KEY("code")
+);
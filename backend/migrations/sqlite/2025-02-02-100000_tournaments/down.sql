@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE pending_registrations
+DROP COLUMN "tournament_id";
+
+ALTER TABLE archer_additions
+DROP COLUMN "tournament_id";
+
+DROP TABLE "tournaments";
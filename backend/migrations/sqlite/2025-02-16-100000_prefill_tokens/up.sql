@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE "prefill_tokens" (
+	"token"	TEXT NOT NULL,
+	"email"	TEXT NOT NULL,
+	"created_at"	TEXT NOT NULL,
+	PRIMARY KEY("token")
+);
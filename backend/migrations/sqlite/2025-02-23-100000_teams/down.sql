@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE archer_additions
+DROP COLUMN "team_id";
+
+DROP TABLE "teams";
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE "teams" (
+	"id"	INTEGER NOT NULL UNIQUE,
+	"tournament_id"	INTEGER NOT NULL,
+	"name"	TEXT NOT NULL,
+	"class"	TEXT NOT NULL,
+	"email"	TEXT NOT NULL,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+
+ALTER TABLE archer_additions
+ADD "team_id" INTEGER;
@@ -0,0 +1,69 @@
+-- This file should undo anything in `up.sql`
+CREATE TABLE "new_tournaments" (
+	"id"	INTEGER NOT NULL UNIQUE,
+	"slug"	TEXT NOT NULL UNIQUE,
+	"name"	TEXT NOT NULL,
+	"date"	TEXT NOT NULL,
+	"venue"	TEXT NOT NULL DEFAULT '',
+	"season_start"	TEXT NOT NULL,
+	"kind"	TEXT NOT NULL DEFAULT 'Indoor',
+	"sessions"	TEXT NOT NULL DEFAULT '[]',
+	"classes"	TEXT NOT NULL DEFAULT '[]',
+	"prices"	TEXT NOT NULL DEFAULT '{}',
+	"registration_opens"	TEXT,
+	"registration_closes"	TEXT,
+	"archived_at"	TEXT,
+	"info_url"	TEXT NOT NULL DEFAULT '',
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "new_tournaments"
+SELECT "id", "slug", "name", date("date"), "venue", date("season_start"),
+	"kind", "sessions", "classes", "prices",
+	strftime('%Y-%m-%d %H:%M:%S', "registration_opens"), strftime('%Y-%m-%d %H:%M:%S', "registration_closes"),
+	strftime('%Y-%m-%d %H:%M:%S', "archived_at"), "info_url"
+FROM "tournaments";
+DROP TABLE "tournaments";
+ALTER TABLE "new_tournaments" RENAME TO "tournaments";
+
+CREATE TABLE "new_mail_queue" (
+	"id"	INTEGER NOT NULL UNIQUE,
+	"recipient"	TEXT NOT NULL,
+	"subject"	TEXT NOT NULL,
+	"body"	TEXT NOT NULL,
+	"created_at"	TEXT NOT NULL,
+	"sent_at"	TEXT,
+	"attempts"	INTEGER NOT NULL DEFAULT 0,
+	"last_error"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "new_mail_queue"
+SELECT "id", "recipient", "subject", "body", strftime('%Y-%m-%d %H:%M:%S', "created_at"), strftime('%Y-%m-%d %H:%M:%S', "sent_at"),
+	"attempts", "last_error"
+FROM "mail_queue";
+DROP TABLE "mail_queue";
+ALTER TABLE "new_mail_queue" RENAME TO "mail_queue";
+
+CREATE TABLE "new_pending_registrations" (
+	"token"	TEXT NOT NULL,
+	"payload"	TEXT NOT NULL,
+	"created_at"	TEXT NOT NULL,
+	"tournament_id"	INTEGER NOT NULL DEFAULT 1,
+	PRIMARY KEY("token")
+);
+INSERT INTO "new_pending_registrations"
+SELECT "token", "payload", strftime('%Y-%m-%d %H:%M:%S', "created_at"), "tournament_id"
+FROM "pending_registrations";
+DROP TABLE "pending_registrations";
+ALTER TABLE "new_pending_registrations" RENAME TO "pending_registrations";
+
+CREATE TABLE "new_prefill_tokens" (
+	"token"	TEXT NOT NULL,
+	"email"	TEXT NOT NULL,
+	"created_at"	TEXT NOT NULL,
+	PRIMARY KEY("token")
+);
+INSERT INTO "new_prefill_tokens"
+SELECT "token", "email", strftime('%Y-%m-%d %H:%M:%S', "created_at")
+FROM "prefill_tokens";
+DROP TABLE "prefill_tokens";
+ALTER TABLE "new_prefill_tokens" RENAME TO "prefill_tokens";
@@ -0,0 +1,70 @@
+-- Your SQL goes here
+-- SQLite can't change the type of a column, so the tables are copied
+CREATE TABLE "new_tournaments" (
+	"id"	INTEGER NOT NULL UNIQUE,
+	"slug"	TEXT NOT NULL UNIQUE,
+	"name"	TEXT NOT NULL,
+	"date"	DATE NOT NULL,
+	"venue"	TEXT NOT NULL DEFAULT '',
+	"season_start"	DATE NOT NULL,
+	"kind"	TEXT NOT NULL DEFAULT 'Indoor',
+	"sessions"	TEXT NOT NULL DEFAULT '[]',
+	"classes"	TEXT NOT NULL DEFAULT '[]',
+	"prices"	TEXT NOT NULL DEFAULT '{}',
+	"registration_opens"	TIMESTAMP,
+	"registration_closes"	TIMESTAMP,
+	"archived_at"	TIMESTAMP,
+	"info_url"	TEXT NOT NULL DEFAULT '',
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "new_tournaments"
+SELECT "id", "slug", "name", date("date"), "venue", date("season_start"),
+	"kind", "sessions", "classes", "prices",
+	datetime("registration_opens"), datetime("registration_closes"),
+	datetime("archived_at"), "info_url"
+FROM "tournaments";
+DROP TABLE "tournaments";
+ALTER TABLE "new_tournaments" RENAME TO "tournaments";
+
+CREATE TABLE "new_mail_queue" (
+	"id"	INTEGER NOT NULL UNIQUE,
+	"recipient"	TEXT NOT NULL,
+	"subject"	TEXT NOT NULL,
+	"body"	TEXT NOT NULL,
+	"created_at"	TIMESTAMP NOT NULL,
+	"sent_at"	TIMESTAMP,
+	"attempts"	INTEGER NOT NULL DEFAULT 0,
+	"last_error"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "new_mail_queue"
+SELECT "id", "recipient", "subject", "body", datetime("created_at"), datetime("sent_at"),
+	"attempts", "last_error"
+FROM "mail_queue";
+DROP TABLE "mail_queue";
+ALTER TABLE "new_mail_queue" RENAME TO "mail_queue";
+
+CREATE TABLE "new_pending_registrations" (
+	"token"	TEXT NOT NULL,
+	"payload"	TEXT NOT NULL,
+	"created_at"	TIMESTAMP NOT NULL,
+	"tournament_id"	INTEGER NOT NULL DEFAULT 1,
+	PRIMARY KEY("token")
+);
+INSERT INTO "new_pending_registrations"
+SELECT "token", "payload", datetime("created_at"), "tournament_id"
+FROM "pending_registrations";
+DROP TABLE "pending_registrations";
+ALTER TABLE "new_pending_registrations" RENAME TO "pending_registrations";
+
+CREATE TABLE "new_prefill_tokens" (
+	"token"	TEXT NOT NULL,
+	"email"	TEXT NOT NULL,
+	"created_at"	TIMESTAMP NOT NULL,
+	PRIMARY KEY("token")
+);
+INSERT INTO "new_prefill_tokens"
+SELECT "token", "email", datetime("created_at")
+FROM "prefill_tokens";
+DROP TABLE "prefill_tokens";
+ALTER TABLE "new_prefill_tokens" RENAME TO "prefill_tokens";
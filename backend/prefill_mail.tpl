@@ -0,0 +1,13 @@
+Hallo,
+
+für diese Mail-Adresse wurden bereits Schützen angemeldet.
+Über folgenden Link kannst du sie für {{tournament.name}} am {{tournament.date}} ins Anmeldeformular übernehmen:
+{{prefill_link}}
+
+Der Link ist {{valid_hours}} Stunden gültig und kann nur einmal verwendet werden.
+
+Falls du den Link nicht angefordert hast, kannst du diese Mail einfach ignorieren.
+
+Viele Grüße und Alle ins Gold
+Tobias Edlböck
+PSV München
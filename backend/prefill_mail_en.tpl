@@ -0,0 +1,13 @@
+Hello,
+
+Archers were registered with this mail address before.
+Use the following link to fill them into the registration form for {{tournament.name}} on {{tournament.date}}:
+{{prefill_link}}
+
+The link is valid for {{valid_hours}} hours and can only be used once.
+
+If you didn't request this link, you can simply ignore this mail.
+
+Kind Regards
+Tobias Edlböck
+PSV München
@@ -0,0 +1,495 @@
+use crate::{config::Config, db, db::DbConnection, error::*, models, schema};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use common::bow_type::BowType;
+use common::class::{Class, Rules};
+use common::duplicate::{ArcherIdentity, DuplicateReason};
+use common::target_face::TargetFace;
+use diesel::prelude::*;
+use handlebars::Handlebars;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+/// Extractor guarding the admin api.
+/// Requests need to carry the configured `admin_token` as bearer token.
+pub struct AdminAuth;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminAuth
+where
+    Arc<Config>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let config = Arc::<Config>::from_ref(state);
+        let Some(token) = &config.admin_token else {
+            return Err((StatusCode::FORBIDDEN, "Admin-Zugang ist deaktiviert"));
+        };
+        let provided = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "));
+        // Constant time, so the token can't be guessed byte by byte from the response time
+        if provided.is_some_and(|provided| provided.as_bytes().ct_eq(token.as_bytes()).into()) {
+            Ok(AdminAuth)
+        } else {
+            Err((StatusCode::UNAUTHORIZED, "Nicht autorisiert"))
+        }
+    }
+}
+
+/// Selects the archers whose registrators receive a bulk mail.
+/// Empty lists don't restrict the selection.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct RecipientFilter {
+    /// Slugs of the tournaments
+    pub tournaments: Vec<String>,
+    /// Numbered from 1 like in Ianseo
+    pub sessions: Vec<i32>,
+    pub classes: Vec<Class>,
+    pub clubs: Vec<String>,
+    pub paid: Option<bool>,
+}
+
+impl RecipientFilter {
+    fn matches(&self, row: &RecipientRow) -> bool {
+        (self.tournaments.is_empty() || self.tournaments.contains(&row.tournament))
+            && (self.sessions.is_empty() || self.sessions.contains(&(row.archer.session + 1)))
+            && (self.classes.is_empty()
+                || self
+                    .classes
+                    .iter()
+                    .any(|cls| cls.code() == row.archer.class))
+            && (self.clubs.is_empty()
+                || self.clubs.iter().any(|club| {
+                    club.trim()
+                        .eq_ignore_ascii_case(row.archer.club_name.trim())
+                }))
+            && self.paid.is_none_or(|paid| paid == row.archer.paid)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BulkMail {
+    pub subject: String,
+    /// Handlebars template, rendered with a [`Recipient`] as data
+    pub body: String,
+    #[serde(default)]
+    pub filter: RecipientFilter,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewRequest {
+    #[serde(flatten)]
+    pub mail: BulkMail,
+    /// Recipient to render the preview for. Defaults to the first one.
+    pub recipient: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PreviewResponse {
+    pub recipients: Vec<String>,
+    pub preview: Option<RenderedMail>,
+}
+
+#[derive(Serialize)]
+pub struct RenderedMail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Template data of a bulk mail: all selected archers registered with the same mail address
+#[derive(Debug, Serialize)]
+pub struct Recipient {
+    pub mail_address: String,
+    pub club: String,
+    pub archers: Vec<RecipientArcher>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecipientArcher {
+    pub bib: i32,
+    pub tournament: String,
+    pub first_name: String,
+    pub last_name: String,
+    /// Numbered from 1 like in Ianseo
+    pub session: i32,
+    pub class: String,
+    pub division: String,
+    pub target: String,
+    pub paid: bool,
+}
+
+struct RecipientRow {
+    archer: models::Archer,
+    tournament: String,
+    mail: String,
+}
+
+pub async fn preview_mail(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+    State(rules): State<Arc<Rules>>,
+    Json(request): Json<PreviewRequest>,
+) -> Result<impl IntoResponse> {
+    let (recipients, request) = db::run(&pool, move |conn| {
+        load_recipients(conn, &request.mail.filter, &rules).map(|recipients| (recipients, request))
+    })
+    .await?;
+    let preview = match &request.recipient {
+        Some(mail) => recipients
+            .iter()
+            .find(|r| r.mail_address.eq_ignore_ascii_case(mail.trim())),
+        None => recipients.first(),
+    }
+    .map(|r| render_mail(&request.mail, r))
+    .transpose()?;
+
+    Ok(Json(PreviewResponse {
+        recipients: recipients.into_iter().map(|r| r.mail_address).collect(),
+        preview,
+    }))
+}
+
+pub async fn enqueue_mail(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+    State(rules): State<Arc<Rules>>,
+    Json(mail): Json<BulkMail>,
+) -> Result<impl IntoResponse> {
+    let enqueued = db::run(&pool, move |conn| {
+        let mails = load_recipients(conn, &mail.filter, &rules)?
+            .iter()
+            .map(|r| render_mail(&mail, r))
+            .collect::<Result<Vec<_>>>()?;
+        crate::mail_queue::enqueue(conn, mails)
+    })
+    .await?;
+    log::info!("Enqueued bulk mail for {} recipients", enqueued);
+
+    Ok((StatusCode::ACCEPTED, Json(enqueued)))
+}
+
+pub async fn set_paid(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+    Path(bib): Path<i32>,
+    Json(paid): Json<bool>,
+) -> Result<impl IntoResponse> {
+    let updated = db::run(&pool, move |conn| {
+        use schema::archers;
+        conn.transaction(|conn| -> Result<usize> {
+            crate::archive::refuse_archived_archer(conn, bib)?;
+            Ok(diesel::update(archers::table.find(bib))
+                .set(archers::paid.eq(paid))
+                .execute(conn)?)
+        })
+    })
+    .await?;
+
+    Ok(if updated == 0 {
+        StatusCode::NOT_FOUND
+    } else {
+        StatusCode::NO_CONTENT
+    })
+}
+
+#[derive(Serialize)]
+pub struct SuspectedDuplicate {
+    pub tournament: String,
+    pub bib: i32,
+    pub duplicate_bib: i32,
+    pub archer: ArcherIdentity,
+    pub existing: ArcherIdentity,
+    pub reason: DuplicateReason,
+}
+
+/// Report of all registered archers which are likely registered more than once for a tournament
+pub async fn list_duplicates(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+) -> Result<impl IntoResponse> {
+    let registered = db::run(&pool, |conn| {
+        crate::tournament::all(conn)?
+            .into_iter()
+            .map(|(id, tournament)| {
+                crate::archer::registered_identities(conn, id)
+                    .map(|archers| (tournament.slug, archers))
+            })
+            .collect::<Result<Vec<_>>>()
+    })
+    .await?;
+    let duplicates: Vec<_> = registered
+        .iter()
+        .flat_map(|(slug, registered)| {
+            registered
+                .iter()
+                .enumerate()
+                .flat_map(move |(index, (bib, identity))| {
+                    find_duplicates_of(slug, *bib, identity, &registered[index + 1..])
+                })
+        })
+        .collect();
+    Ok(Json(duplicates))
+}
+
+fn find_duplicates_of<'a>(
+    slug: &'a str,
+    bib: i32,
+    identity: &'a ArcherIdentity,
+    others: &'a [(i32, ArcherIdentity)],
+) -> impl Iterator<Item = SuspectedDuplicate> + 'a {
+    others.iter().filter_map(move |(duplicate_bib, other)| {
+        identity
+            .duplicate_reason(other)
+            .map(|reason| SuspectedDuplicate {
+                tournament: slug.to_string(),
+                bib,
+                duplicate_bib: *duplicate_bib,
+                archer: identity.clone(),
+                existing: other.clone(),
+                reason,
+            })
+    })
+}
+
+#[derive(Serialize)]
+pub struct UnknownClub {
+    pub name: String,
+    pub bibs: Vec<i32>,
+}
+
+/// Clubs given at registration which couldn't be found in the club registry.
+/// Archers of archived tournaments are left out, they can't be changed anymore.
+pub async fn list_unknown_clubs(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+) -> Result<impl IntoResponse> {
+    let unknown = db::run(&pool, |conn| -> Result<Vec<(i32, String)>> {
+        use schema::archers;
+        Ok(archers::table
+            .filter(archers::club_code.is_null())
+            .filter(archers::registration_id.eq_any(unarchived_registrations()))
+            .select((archers::bib, archers::club_name))
+            .order(archers::club_name)
+            .load(conn)?)
+    })
+    .await?;
+
+    let mut clubs = BTreeMap::<String, Vec<i32>>::new();
+    for (bib, name) in unknown {
+        clubs.entry(name).or_default().push(bib);
+    }
+    Ok(Json(
+        clubs
+            .into_iter()
+            .map(|(name, bibs)| UnknownClub { name, bibs })
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ResolveClub {
+    /// Club name as given at registration
+    pub name: String,
+    pub code: String,
+}
+
+/// Ids of the registrations for tournaments which aren't archived
+#[diesel::dsl::auto_type(no_type_alias)]
+fn unarchived_registrations() -> _ {
+    schema::registrations::table
+        .filter(
+            schema::registrations::tournament_id.eq_any(
+                schema::tournaments::table
+                    .filter(schema::tournaments::archived_at.is_null())
+                    .select(schema::tournaments::id),
+            ),
+        )
+        .select(schema::registrations::id)
+}
+
+/// Assigns a registered club to all archers registered with an unknown club name.
+/// Archers of archived tournaments keep theirs.
+pub async fn resolve_club(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+    Json(resolve): Json<ResolveClub>,
+) -> Result<impl IntoResponse> {
+    let updated = db::run(&pool, move |conn| -> Result<Option<usize>> {
+        use schema::{archers, clubs};
+        let Some(club) = clubs::table
+            .find(&resolve.code)
+            .first::<models::Club>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let updated = diesel::update(
+            archers::table
+                .filter(archers::club_name.eq(&resolve.name))
+                .filter(archers::club_code.is_null())
+                .filter(archers::registration_id.eq_any(unarchived_registrations())),
+        )
+        .set((
+            archers::club_code.eq(club.code),
+            archers::club_name.eq(club.name),
+        ))
+        .execute(conn)?;
+        let archived: i64 = archers::table
+            .filter(archers::club_name.eq(&resolve.name))
+            .filter(archers::club_code.is_null())
+            .count()
+            .get_result(conn)?;
+        if updated == 0 && archived > 0 {
+            return Err(Error::ArchivedError);
+        }
+        Ok(Some(updated))
+    })
+    .await?;
+
+    Ok(match updated {
+        Some(updated) => (StatusCode::OK, Json(updated)).into_response(),
+        None => (StatusCode::NOT_FOUND, "Unbekannter Verein").into_response(),
+    })
+}
+
+fn render_mail(mail: &BulkMail, recipient: &Recipient) -> Result<RenderedMail> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    Ok(RenderedMail {
+        to: recipient.mail_address.clone(),
+        subject: handlebars.render_template(&mail.subject, recipient)?,
+        body: handlebars.render_template(&mail.body, recipient)?,
+    })
+}
+
+fn load_recipients(
+    connection: &mut DbConnection,
+    filter: &RecipientFilter,
+    rules: &Rules,
+) -> Result<Vec<Recipient>> {
+    use schema::{archers, registrations, tournaments};
+    let rows: Vec<(models::Archer, String, Option<String>)> = archers::table
+        .inner_join(registrations::table.inner_join(tournaments::table))
+        .order(archers::bib)
+        .select((
+            models::Archer::as_select(),
+            tournaments::slug,
+            registrations::email,
+        ))
+        .load(connection)?;
+
+    Ok(group_recipients(
+        rows.into_iter()
+            .filter_map(|(archer, tournament, mail)| {
+                Some(RecipientRow {
+                    archer,
+                    tournament,
+                    mail: mail?,
+                })
+            })
+            .filter(|row| filter.matches(row)),
+        rules,
+    ))
+}
+
+/// Groups archers by registrator mail, so everybody receives a bulk mail only once
+fn group_recipients(rows: impl Iterator<Item = RecipientRow>, rules: &Rules) -> Vec<Recipient> {
+    let mut recipients = BTreeMap::<String, Recipient>::new();
+    for row in rows {
+        let recipient = recipients
+            .entry(row.mail.trim().to_lowercase())
+            .or_insert_with(|| Recipient {
+                mail_address: row.mail.trim().to_string(),
+                club: row.archer.club_name.clone(),
+                archers: Vec::new(),
+            });
+        recipient.archers.push(RecipientArcher {
+            bib: row.archer.bib,
+            tournament: row.tournament,
+            first_name: row.archer.first_name,
+            last_name: row.archer.last_name,
+            session: row.archer.session + 1,
+            class: rules
+                .class_from_code(&row.archer.class)
+                .map(|cls| cls.name(common::locale::Locale::De).to_string())
+                .unwrap_or(row.archer.class),
+            division: db::parse_enum_code::<BowType>(&row.archer.bow_type)
+                .map_or(row.archer.bow_type, |bow_type| {
+                    bow_type.ianseo_division().to_string()
+                }),
+            target: TargetFace::from_str(&row.archer.target_face)
+                .map_or(row.archer.target_face, |tf| tf.to_string()),
+            paid: row.archer.paid,
+        });
+    }
+    recipients.into_values().collect()
+}
+
+#[test]
+fn test_group_recipients_by_mail() {
+    let row = |bib: i32, mail: &str| RecipientRow {
+        archer: models::Archer {
+            bib,
+            registration_id: 1,
+            team_id: None,
+            session: 0,
+            first_name: "Foo".into(),
+            last_name: "Bar".into(),
+            date_of_birth: chrono::NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            gender: None,
+            bow_type: "Recurve".into(),
+            class: "RUE20M".into(),
+            target_face: "M18cm40".into(),
+            club_code: None,
+            club_name: "PSV".into(),
+            paid: false,
+            individual_final: true,
+            team_final: true,
+            mixed_team_final: true,
+        },
+        tournament: "indoor25".into(),
+        mail: mail.into(),
+    };
+    let recipients = group_recipients(
+        [
+            row(1, "foo@bar.com"),
+            row(2, "other@bar.com"),
+            row(3, " Foo@Bar.com"),
+        ]
+        .into_iter(),
+        &Rules::default(),
+    );
+    assert_eq!(recipients.len(), 2);
+    assert_eq!(recipients[0].mail_address, "foo@bar.com");
+    assert_eq!(
+        recipients[0]
+            .archers
+            .iter()
+            .map(|a| a.bib)
+            .collect::<Vec<_>>(),
+        vec![1, 3]
+    );
+    assert_eq!(recipients[0].archers[0].class, "Recurve Herren");
+    assert_eq!(recipients[0].archers[0].target, "18m / 40cm");
+    assert_eq!(recipients[0].archers[0].division, "R");
+    assert_eq!(recipients[0].archers[0].session, 1);
+    assert_eq!(recipients[1].archers.len(), 1);
+}
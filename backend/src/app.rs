@@ -0,0 +1,261 @@
+use crate::config::Config;
+use crate::mail::Mailer;
+use crate::spam::{FormSigner, RateLimiter};
+use crate::{admin, archer, club, confirmation, db, ianseo, prefill, spam, tournament};
+use axum::{
+    body::{boxed, Body, BoxBody},
+    extract::FromRef,
+    http::{Request, Response, StatusCode, Uri},
+    middleware,
+    response::AppendHeaders,
+    routing::{get, post, put},
+    Router,
+};
+use common::class::Rules;
+use handlebars::Handlebars;
+use lazy_static::lazy_static;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::watch;
+use tower::ServiceExt;
+use tower_http::services::ServeDir;
+
+/// State shared by the handlers and background tasks.
+/// Reloading the config replaces it as a whole, so a request never sees a mix of old and new.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub rules: Arc<Rules>,
+    pub templates: Arc<Handlebars<'static>>,
+    pub pool: db::Pool,
+    pub mailer: Mailer,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub form_signer: Arc<FormSigner>,
+}
+
+impl AppState {
+    pub fn new(
+        config: Config,
+        rules: Rules,
+        pool: db::Pool,
+    ) -> Result<AppState, Box<dyn std::error::Error>> {
+        Ok(AppState {
+            templates: Arc::new(load_templates()?),
+            mailer: Mailer::new(&config.mail_server)?,
+            rate_limiter: Arc::new(RateLimiter::new(Duration::from_secs(
+                config.spam_protection.rate_limit_window_minutes * 60,
+            ))),
+            form_signer: Arc::new(FormSigner::generate()),
+            config: Arc::new(config),
+            rules: Arc::new(rules),
+            pool,
+        })
+    }
+
+    /// State with a reloaded config and rules and the same database.
+    /// The rate limits carry over unless their window changed, issued form stamps stay valid.
+    pub fn reload(
+        &self,
+        config: Config,
+        rules: Rules,
+    ) -> Result<AppState, Box<dyn std::error::Error>> {
+        let mut state = AppState::new(config, rules, self.pool.clone())?;
+        state.form_signer = self.form_signer.clone();
+        if state.config.spam_protection.rate_limit_window_minutes
+            == self.config.spam_protection.rate_limit_window_minutes
+        {
+            state.rate_limiter = self.rate_limiter.clone();
+        }
+        Ok(state)
+    }
+}
+
+pub fn load_templates() -> Result<Handlebars<'static>, Box<handlebars::TemplateError>> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    handlebars.set_dev_mode(cfg!(debug_assertions));
+    handlebars.register_template_string("user_mail_en", include_str!("../user_mail_en.tpl"))?;
+    handlebars.register_template_string("user_mail", include_str!("../user_mail.tpl"))?;
+    handlebars
+        .register_template_string("confirm_mail_en", include_str!("../confirm_mail_en.tpl"))?;
+    handlebars.register_template_string("confirm_mail", include_str!("../confirm_mail.tpl"))?;
+    handlebars
+        .register_template_string("prefill_mail_en", include_str!("../prefill_mail_en.tpl"))?;
+    handlebars.register_template_string("prefill_mail", include_str!("../prefill_mail.tpl"))?;
+    Ok(handlebars)
+}
+
+/// Logs whether mails can be sent. The server keeps running if not, the mail server may come back.
+pub async fn check_mailer(mailer: &Mailer) {
+    match mailer.test_connection().await {
+        Ok(true) => log::info!("Mails are sent to {}", mailer),
+        Ok(false) => log::error!("{} didn't respond, mails can't be sent", mailer),
+        Err(e) => log::error!("Couldn't connect to {}, mails can't be sent: {}", mailer, e),
+    }
+}
+
+/// Api and frontend served with the state
+pub fn build_app(state: AppState) -> Router {
+    let api = Router::new()
+        .route("/tournaments", get(tournament::list_tournaments))
+        .route("/t/:slug", get(tournament::get_tournament))
+        .route(
+            "/t/:slug/archers",
+            post(archer::create_archers).layer(middleware::from_fn_with_state(
+                state.clone(),
+                spam::rate_limit,
+            )),
+        )
+        .route("/t/:slug/archers", get(archer::list_archers))
+        .route(
+            "/t/:slug/prefill",
+            post(prefill::request_prefill).layer(middleware::from_fn_with_state(
+                state.clone(),
+                spam::rate_limit,
+            )),
+        )
+        .route("/prefill/:token", get(prefill::get_prefill))
+        .route("/clubs", get(club::search_clubs))
+        .route("/rules", get(archer::get_rules))
+        .route("/form-stamp", get(spam::get_form_stamp))
+        .route(
+            "/registrations/confirm/:token",
+            get(confirmation::show_confirmation).post(confirmation::confirm_registration),
+        )
+        .route("/admin/tournaments/:slug", put(tournament::put_tournament))
+        .route(
+            "/admin/tournaments/:slug/ianseo",
+            get(ianseo::export_archers),
+        )
+        .route("/admin/mails/preview", post(admin::preview_mail))
+        .route("/admin/mails", post(admin::enqueue_mail))
+        .route("/admin/archers/:bib/paid", put(admin::set_paid))
+        .route("/admin/duplicates", get(admin::list_duplicates))
+        .route("/admin/clubs/unknown", get(admin::list_unknown_clubs))
+        .route("/admin/clubs/resolve", post(admin::resolve_club))
+        .with_state(state);
+    Router::new()
+        .nest_service(
+            "/",
+            get(handler).then(|res| async move {
+                Ok((
+                    AppendHeaders([(axum::http::header::CACHE_CONTROL, "no-cache")]),
+                    res,
+                ))
+            }),
+        )
+        .nest_service("/api", api)
+}
+
+/// App built from the current state. The router isn't `Sync`, the mutex is only held to clone it.
+pub type SharedApp = Mutex<Router>;
+
+/// Hands every request to the app that is current when the request arrives
+pub fn current_app(apps: watch::Receiver<SharedApp>) -> Router {
+    Router::new().fallback(move |request: Request<Body>| {
+        let app = apps.borrow().lock().unwrap().clone();
+        app.oneshot(request)
+    })
+}
+
+async fn handler(uri: Uri) -> Result<Response<BoxBody>, (StatusCode, String)> {
+    // Tournament pages are routed by the frontend
+    if uri.path().starts_with("/t/") {
+        return get_static_file(Uri::from_static("/index.html")).await;
+    }
+    let res = get_static_file(uri.clone()).await?;
+
+    if res.status() == StatusCode::NOT_FOUND {
+        // try with `.html`
+        // TODO: handle if the Uri has query parameters
+        match format!("{}.html", uri).parse() {
+            Ok(uri_html) => get_static_file(uri_html).await,
+            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Invalid URI".to_string())),
+        }
+    } else {
+        Ok(res)
+    }
+}
+
+async fn get_static_file(uri: Uri) -> Result<Response<BoxBody>, (StatusCode, String)> {
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    lazy_static! {
+        static ref HTML_PATH: String =
+            std::env::var("WEBPAGE").unwrap_or_else(|_| "../frontend/dist".to_string());
+    }
+
+    // `ServeDir` implements `tower::Service` so we can call it with `tower::ServiceExt::oneshot`
+    match ServeDir::new(&*HTML_PATH).oneshot(req).await {
+        Ok(res) => Ok(res.map(boxed)),
+        Err(err) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Something went wrong: {}", err),
+        )),
+    }
+}
+
+/// Config for handler tests: mails go to stdout and forms can be sent right away
+#[cfg(all(test, feature = "sqlite"))]
+pub fn test_config() -> Config {
+    Config {
+        mail_server: crate::config::MailServerConfig {
+            transport: crate::config::MailTransport::Stdout,
+            ..Default::default()
+        },
+        spam_protection: crate::config::SpamProtectionConfig {
+            min_fill_seconds: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// State on a migrated SQLite database in a temporary file, for handler tests.
+/// Remove the database with [`remove_test_database`] once the state is dropped.
+#[cfg(all(test, feature = "sqlite"))]
+pub fn test_state(name: &str, config: Config) -> (AppState, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!("{}-test-{}.sqlite", name, std::process::id()));
+    remove_test_database(&path);
+    let pool = db::create_pool(path.to_str().unwrap()).unwrap();
+    db::migrate(&mut pool.get().unwrap()).unwrap();
+    (AppState::new(config, Rules::default(), pool).unwrap(), path)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+pub fn remove_test_database(path: &std::path::Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_reload_swaps_state() {
+    let config = |admin_token: &str| Config {
+        admin_token: Some(admin_token.into()),
+        ..test_config()
+    };
+    let (state, path) = test_state("app", config("old"));
+    let (apps, current) = watch::channel(Mutex::new(build_app(state.clone())));
+    let app = current_app(current);
+    let duplicates = |token: &str| {
+        Request::get("/api/admin/duplicates")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let status = |request| async { app.clone().oneshot(request).await.unwrap().status() };
+    assert_eq!(status(duplicates("old")).await, StatusCode::OK);
+
+    let reloaded = state.reload(config("new"), Rules::default()).unwrap();
+    assert!(Arc::ptr_eq(&reloaded.rate_limiter, &state.rate_limiter));
+    assert!(Arc::ptr_eq(&reloaded.form_signer, &state.form_signer));
+    apps.send_replace(Mutex::new(build_app(reloaded)));
+    assert_eq!(status(duplicates("old")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(status(duplicates("new")).await, StatusCode::OK);
+    drop(state);
+    remove_test_database(&path);
+}
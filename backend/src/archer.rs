@@ -1,29 +1,211 @@
-use crate::{error::*, schema, CONFIG, HANDLEBARS};
-use axum::{http::StatusCode, response::IntoResponse, Json};
-use common::archer::{Archer, RegisteredArcher};
-use common::class::Class;
+use crate::app::AppState;
+use crate::tournament::{CurrentTournament, MailTournament};
+use crate::{db, db::DbConnection, error::*, schema, spam::SubmissionCheck};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use common::archer::{Archer, ArcherError, Finals, RegisteredArcher};
+use common::class::Rules;
+use common::club::Club;
+use common::duplicate::{ArcherIdentity, DuplicateWarning};
 use common::line_data::CreateArchersPayload;
+use common::target_face::TargetFace;
+use common::team::{Team, TeamClassDefinition};
+use common::tournament::Tournament;
 use diesel::prelude::*;
 use lettre::message::Mailbox;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
-use log::warn;
+use std::str::FromStr;
+use std::sync::Arc;
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn create_archers(
+    State(state): State<AppState>,
+    current: CurrentTournament,
     Json(payload): Json<CreateArchersPayload>,
 ) -> Result<impl IntoResponse> {
-    // println!("Received {} {}", payload.first_name, payload.last_name);
+    if !current
+        .tournament
+        .is_open(chrono::Local::now().naive_local())
+    {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            "Die Anmeldung ist geschlossen.".into_response(),
+        ));
+    }
+
+    match crate::spam::check_submission(&payload, &state.config.spam_protection, &state.form_signer)
+    {
+        SubmissionCheck::Ok => (),
+        SubmissionCheck::Honeypot => {
+            log::warn!(
+                "Rejected registration for {}: honeypot filled",
+                payload.mail
+            );
+            // Pretend success, so bots don't learn about the check
+            return Ok((StatusCode::CREATED, Json(payload).into_response()));
+        }
+        SubmissionCheck::TooFast(seconds) => {
+            log::warn!(
+                "Rejected registration for {}: form filled in {}s",
+                payload.mail,
+                seconds
+            );
+            return Ok((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Formular zu schnell abgeschickt. Bitte versuche es erneut.".into_response(),
+            ));
+        }
+        SubmissionCheck::InvalidStamp => {
+            log::warn!(
+                "Rejected registration for {}: invalid form stamp",
+                payload.mail
+            );
+            return Ok((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Das Formular ist abgelaufen. Bitte lade die Seite neu.".into_response(),
+            ));
+        }
+    }
+
+    if let Err(message) = check_registration(&payload, &state.rules, &current.tournament) {
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, message.into_response()));
+    }
+
+    if !payload.ignore_duplicates {
+        let archers = payload.archers.clone();
+        let tournament_id = current.id;
+        let duplicates = db::run(&state.pool, move |conn| {
+            find_duplicates(conn, &archers, tournament_id)
+        })
+        .await?;
+        if !duplicates.is_empty() {
+            log::info!(
+                "Rejected registration with {} suspected duplicates",
+                duplicates.len()
+            );
+            return Ok((StatusCode::CONFLICT, Json(duplicates).into_response()));
+        }
+    }
+
+    if state.config.double_opt_in.is_some() {
+        crate::confirmation::request_confirmation(&state, &payload, &current).await?;
+        return Ok((StatusCode::ACCEPTED, Json(payload).into_response()));
+    }
+    register_archers(&state, &payload, &current, None).await?;
+
+    Ok((StatusCode::CREATED, Json(payload).into_response()))
+}
+
+/// Checks the archers and teams of a registration against the rules and the tournament.
+/// Returns the message for the registrator if they don't fit.
+pub fn check_registration(
+    payload: &CreateArchersPayload,
+    rules: &Rules,
+    tournament: &Tournament,
+) -> std::result::Result<(), String> {
+    if let Some((archer, e)) = payload.archers.iter().find_map(|archer| {
+        archer
+            .validate(rules, tournament)
+            .err()
+            .map(|e| (archer, e))
+    }) {
+        log::warn!("Rejected registration with class {}: {}", archer.class(), e);
+        let name = format!("{} {}", archer.first_name, archer.last_name);
+        return Err(match e {
+            ArcherError::Class => format!(
+                "Die Klasse {} ist für {} nicht möglich.",
+                archer.class(),
+                name
+            ),
+            ArcherError::TargetFace | ArcherError::Session => {
+                "Unbekannte Klasse, Scheibe oder Termin. Bitte lade die Seite neu.".into()
+            }
+            ArcherError::MissingGender => format!("Bitte gib das Geschlecht von {} an.", name),
+            ArcherError::Gender => format!(
+                "Das Geschlecht von {} passt nicht zur Klasse {}.",
+                name,
+                archer.class()
+            ),
+            ArcherError::Finals => format!(
+                "Die Klasse {} kann nicht an Finals teilnehmen.",
+                archer.class()
+            ),
+        });
+    }
 
-    // if payload.archers.iter().any(|a| [0, 1].contains(&a.session)) {
-    //     return Ok((
-    //         StatusCode::FORBIDDEN,
-    //         Json("Registration closed").into_response(),
-    //     ));
-    // }
+    if let Err((index, e)) = rules.validate_teams(&payload.teams, &payload.archers, tournament) {
+        log::warn!(
+            "Rejected registration with invalid team {}: {}",
+            index + 1,
+            e
+        );
+        return Err(format!(
+            "Ungültige Mannschaft {}. Bitte prüfe die Mannschaften.",
+            index + 1
+        ));
+    }
+
+    if let Some(archer) = payload
+        .archers
+        .iter()
+        .enumerate()
+        .find_map(|(index, archer)| {
+            (archer.finals().team && !payload.teams.iter().any(|t| t.members.contains(&index)))
+                .then_some(archer)
+        })
+    {
+        log::warn!(
+            "Rejected registration of {} {} for the team final without a team",
+            archer.first_name,
+            archer.last_name
+        );
+        return Err(
+            "Das Mannschaftsfinale ist nur für Mitglieder einer Mannschaft möglich.".into(),
+        );
+    }
+    Ok(())
+}
 
-    let total_price: u32 = payload.archers.iter().map(|a| a.class().price()).sum();
+/// Stores the archers and teams in one transaction and sends the registration mail.
+/// A confirmed registration passes the token of its pending registration, which is removed in
+/// the same transaction. Returns false without registering if it's gone already.
+/// A failed mail is only logged, as the archers are registered anyway.
+pub async fn register_archers(
+    state: &AppState,
+    payload: &CreateArchersPayload,
+    current: &CurrentTournament,
+    pending_token: Option<String>,
+) -> Result<bool> {
+    let rules = state.rules.clone();
+    let tournament = &current.tournament;
+    let teams: Vec<(Team, TeamClassDefinition)> =
+        match rules.validate_teams(&payload.teams, &payload.archers, tournament) {
+            Ok(classes) => payload
+                .teams
+                .iter()
+                .cloned()
+                .zip(classes.into_iter().cloned())
+                .collect(),
+            Err((index, e)) => {
+                // Rules changed since the registration was submitted
+                log::warn!(
+                    "Dropped invalid team {} of {}: {}",
+                    index + 1,
+                    payload.mail,
+                    e
+                );
+                Vec::new()
+            }
+        };
+    let total_price: u32 = payload
+        .archers
+        .iter()
+        .map(|a| price(a, &rules, tournament))
+        .sum::<u32>()
+        + teams
+            .iter()
+            .map(|(_, cls)| tournament.team_price(cls))
+            .sum::<u32>();
     let mail_data = EmailData {
+        tournament: MailTournament::new(tournament, payload.locale),
         comment: payload.comment.clone(),
         club: payload.club.clone(),
         mail_address: payload.mail.to_string(),
@@ -31,118 +213,249 @@ pub async fn create_archers(
         archers: payload
             .archers
             .iter()
-            .map(|a| EmailArcher::from(a, payload.locale))
+            .map(|a| EmailArcher::from(a, payload.locale, &rules, tournament))
             .collect(),
-        total_price: format!("{},{:02}€", total_price / 100, total_price % 100),
+        teams: teams
+            .iter()
+            .map(|(team, cls)| EmailTeam {
+                name: team.name.clone(),
+                class: cls.name(payload.locale).into(),
+                members: team
+                    .members
+                    .iter()
+                    .map(|&m| {
+                        let archer = &payload.archers[m];
+                        format!("{} {}", archer.first_name, archer.last_name)
+                    })
+                    .collect(),
+                price: format_price(tournament.team_price(cls)),
+            })
+            .collect(),
+        total_price: format_price(total_price),
+        payment: state.config.payment.clone(),
     };
 
+    let registration = crate::models::InsertableRegistration {
+        tournament_id: current.id,
+        name: payload.name.clone(),
+        email: payload.mail.to_string(),
+        club: payload.club.clone(),
+        comment: payload.comment.clone(),
+        locale: db::enum_code(&payload.locale),
+        created_at: db::now(),
+    };
     let archers = payload.archers.clone();
-    let save_task = tokio::task::spawn_blocking(move || {
-        archers
-            .into_iter()
-            .map(|archer| save_archer(archer))
-            .collect::<Result<Vec<()>>>()
-    });
-    let (save, mail) = tokio::join!(save_task, send_registration_mail(mail_data, payload.locale));
-    save.unwrap()?;
-    mail?;
-
-    Ok((StatusCode::CREATED, Json(payload).into_response()))
+    let timeout_hours = crate::confirmation::pending_timeout_hours(&state.config);
+    let saved = db::run(&state.pool, move |conn| -> Result<bool> {
+        let clubs = crate::club::all_clubs(conn)?;
+        conn.transaction(|conn| {
+            if let Some(token) = pending_token {
+                if !crate::confirmation::take_pending(conn, &token, timeout_hours)? {
+                    return Ok(false);
+                }
+            }
+            let registration_id = save_registration(conn, &registration)?;
+            let bibs = archers
+                .into_iter()
+                .enumerate()
+                .map(|(index, archer)| {
+                    let in_team = teams.iter().any(|(team, _)| team.members.contains(&index));
+                    save_archer(conn, archer, in_team, registration_id, &clubs, &rules)
+                })
+                .collect::<Result<Vec<i32>>>()?;
+            for (team, cls) in teams {
+                let members: Vec<i32> = team.members.iter().map(|&m| bibs[m]).collect();
+                save_team(conn, team, &cls, &members, registration_id)?;
+            }
+            Ok(true)
+        })
+    })
+    .await?;
+    if saved {
+        // Only mailed once saved, the mail confirms the registration. Failing the request
+        // would make the registrator submit it again.
+        if let Err(e) = send_registration_mail(state, mail_data, payload.locale).await {
+            log::error!(
+                "Registered archers of {}, but couldn't send the registration mail, \
+                 resend it with the resend-mail command: {}",
+                payload.mail,
+                e
+            );
+        }
+    }
+    Ok(saved)
 }
 
-pub async fn list_archers() -> Result<impl IntoResponse> {
-    Ok(Json(get_archers()?))
+pub fn find_duplicates(
+    connection: &mut DbConnection,
+    archers: &[Archer],
+    tournament_id: i32,
+) -> Result<Vec<DuplicateWarning>> {
+    let registered: Vec<_> = registered_identities(connection, tournament_id)?
+        .into_iter()
+        .map(|(_, identity)| identity)
+        .collect();
+    Ok(archers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, archer)| {
+            archer
+                .identity()
+                .find_duplicate(&registered)
+                .map(|reason| DuplicateWarning {
+                    archer: index,
+                    reason,
+                })
+        })
+        .collect())
 }
 
-fn save_archer(archer: Archer) -> Result<()> {
-    let mut connection = crate::db::establish_connection();
-    connection.transaction(|conn| -> Result<()> {
-        let inserted_bib: i32 = diesel::insert_into(schema::archers::table)
-            .values(crate::models::InsertableArcher {
-                session: archer.session as i32 + 1,
-                division: match archer.class() {
-                    c if Class::recurve_classes().contains(&c) => "R".to_string(),
-                    c if Class::barebow_classes().contains(&c) => "B".to_string(),
-                    c if Class::compound_classes().contains(&c) => "C".to_string(),
-                    _ => unreachable!(),
+/// Bib and identity of all archers registered for the tournament
+pub fn registered_identities(
+    connection: &mut DbConnection,
+    tournament_id: i32,
+) -> Result<Vec<(i32, ArcherIdentity)>> {
+    use crate::schema::{archers, registrations};
+    let rows: Vec<(i32, String, String, chrono::NaiveDate, String)> = archers::table
+        .inner_join(registrations::table)
+        .filter(registrations::tournament_id.eq(tournament_id))
+        .select((
+            archers::bib,
+            archers::first_name,
+            archers::last_name,
+            archers::date_of_birth,
+            archers::club_name,
+        ))
+        .load(connection)?;
+    Ok(rows
+        .into_iter()
+        .map(|(bib, first_name, last_name, date_of_birth, club)| {
+            (
+                bib,
+                ArcherIdentity {
+                    first_name,
+                    last_name,
+                    date_of_birth,
+                    club,
                 },
-                class: format!("{:?}", archer.class()),
-                individual_qualification: 1,
-                team_qualification: 1,
-                individual_final: 1,
-                team_final: 1,
-                mixed_team_final: 1,
-                last_name: archer.last_name.clone(),
-                first_name: archer.first_name.clone(),
-                gender: None,
-                country_code: "TODO".to_string(),
-                country_name: archer.club.clone(),
-                date_of_birth: archer.date_of_birth().format("%Y-%m-%d").to_string(),
-                ..Default::default()
-            })
-            .returning(schema::archers::bib)
-            .get_result(conn)?;
+            )
+        })
+        .collect())
+}
 
-        diesel::insert_into(schema::archer_additions::table)
-            .values(crate::models::ArcherAdditions {
-                bib: inserted_bib,
-                email: archer.mail.as_str().to_owned(),
-                target_face: format!("{:?}", archer.target_face()),
-                comment: archer.comment,
-            })
-            .execute(conn)?;
+pub async fn list_archers(
+    State(pool): State<db::Pool>,
+    current: CurrentTournament,
+) -> Result<impl IntoResponse> {
+    let archers = db::run(&pool, move |conn| get_archers(conn, current.id)).await?;
+    Ok(Json(archers))
+}
 
-        Ok(())
-    })
+pub async fn get_rules(State(rules): State<Arc<Rules>>) -> impl IntoResponse {
+    Json(Rules::clone(&rules))
+}
+
+/// Price of the archer's class at the tournament in euro cent
+fn price(archer: &Archer, rules: &Rules, tournament: &Tournament) -> u32 {
+    rules
+        .class(archer.class())
+        .map_or(0, |cls| tournament.price(cls))
+}
+
+/// Price in euro cent formatted for the mails
+fn format_price(cents: u32) -> String {
+    format!("{},{:02}€", cents / 100, cents % 100)
+}
+
+/// Stores the submitted form and returns its id
+fn save_registration(
+    connection: &mut DbConnection,
+    registration: &crate::models::InsertableRegistration,
+) -> Result<i32> {
+    Ok(diesel::insert_into(schema::registrations::table)
+        .values(registration)
+        .returning(schema::registrations::id)
+        .get_result(connection)?)
+}
+
+/// Stores the archer and returns its bib
+fn save_archer(
+    connection: &mut DbConnection,
+    archer: Archer,
+    in_team: bool,
+    registration_id: i32,
+    clubs: &[Club],
+    rules: &Rules,
+) -> Result<i32> {
+    let club = clubs.iter().find(|club| club.is_named(&archer.club));
+    if club.is_none() {
+        log::info!("Unknown club {:?} needs review", archer.club);
+    }
+    let cls = rules.class(archer.class());
+    Ok(diesel::insert_into(schema::archers::table)
+        .values(crate::models::InsertableArcher {
+            registration_id,
+            session: archer.session.into(),
+            first_name: archer.first_name.clone(),
+            last_name: archer.last_name.clone(),
+            date_of_birth: archer.date_of_birth(),
+            gender: cls
+                .and_then(|cls| cls.gender)
+                .or(archer.gender())
+                .map(|gender| db::enum_code(&gender)),
+            bow_type: db::enum_code(&cls.map(|cls| cls.bow_type).unwrap_or_default()),
+            class: archer.class().to_string(),
+            target_face: db::enum_code(&archer.target_face()),
+            club_code: club.map(|club| club.code.clone()),
+            club_name: club.map_or(archer.club.clone(), |club| club.name.clone()),
+            individual_final: archer.finals().individual,
+            team_final: in_team && archer.finals().team,
+            mixed_team_final: archer.finals().mixed_team,
+        })
+        .returning(schema::archers::bib)
+        .get_result(connection)?)
+}
+
+fn save_team(
+    connection: &mut DbConnection,
+    team: Team,
+    cls: &TeamClassDefinition,
+    members: &[i32],
+    registration_id: i32,
+) -> Result<()> {
+    use schema::{archers, teams};
+    let team_id: i32 = diesel::insert_into(teams::table)
+        .values(crate::models::InsertableTeam {
+            registration_id,
+            name: team.name,
+            class: cls.code.clone(),
+        })
+        .returning(teams::id)
+        .get_result(connection)?;
+    diesel::update(archers::table.filter(archers::bib.eq_any(members)))
+        .set(archers::team_id.eq(team_id))
+        .execute(connection)?;
+    Ok(())
 }
 
 async fn send_registration_mail(
+    state: &AppState,
     email_data: EmailData,
     locale: common::locale::Locale,
 ) -> Result<()> {
-    let credentials = Credentials::new(
-        CONFIG.read().mail_server.smtp_username.clone(),
-        CONFIG.read().mail_server.smtp_password.clone(),
-    );
-    let email = Message::builder()
-        .from(Mailbox::new(
-            Some(CONFIG.read().mail_message.sender_name.clone()),
-            CONFIG
-                .read()
-                .mail_message
-                .sender_address
-                .as_str()
-                .parse()
-                .map_err(|e| {
-                    warn!("received wrong email format: {:?}", e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "Falsches Email Format!".to_string(),
-                    )
-                })
-                .unwrap(),
-        ))
-        .reply_to(Mailbox::new(
-            Some("Tobias Edlböck".to_string()),
-            "indoor@bogen-psv.de".parse().unwrap(),
-        ))
-        .to(Mailbox::new(
-            Some(email_data.name.clone()),
-            email_data.mail_address.parse().unwrap(),
-        ))
-        .bcc(Mailbox::new(
-            Some("Thomas Frank".to_string()),
-            "sport@bogen-psv.de".parse().unwrap(),
-        ))
-        .bcc(Mailbox::new(
-            Some("Tobias Edlböck".to_string()),
-            "indoor@bogen-psv.de".parse().unwrap(),
-        ))
+    let mut email = crate::mail::message_builder(&state.config.mail_message)?.to(Mailbox::new(
+        Some(email_data.name.clone()),
+        email_data.mail_address.parse()?,
+    ));
+    for bcc in &state.config.mail_message.bcc {
+        email = email.bcc(bcc.parse()?);
+    }
+    let email = email
         .header(lettre::message::header::ContentType::TEXT_PLAIN)
-        .subject(&CONFIG.read().mail_message.subject)
+        .subject(&state.config.mail_message.subject)
         .body(
-            HANDLEBARS
-                .read()
+            state
+                .templates
                 .render(
                     match locale {
                         common::locale::Locale::En => "user_mail_en",
@@ -154,22 +467,183 @@ async fn send_registration_mail(
         )
         .unwrap();
 
-    let mailer: AsyncSmtpTransport<Tokio1Executor> =
-        AsyncSmtpTransport::<Tokio1Executor>::relay(&CONFIG.read().mail_server.smtp_server)
-            .unwrap()
-            .credentials(credentials)
-            .build();
-    mailer.send(email).await?;
-    Ok(())
+    state.mailer.send(email).await
+}
+
+fn get_archers(connection: &mut DbConnection, tournament_id: i32) -> Result<Vec<RegisteredArcher>> {
+    Ok(archers_of(connection, tournament_id)?
+        .into_iter()
+        .map(|a| a.into())
+        .collect())
+}
+
+/// All archers registered for the tournament, ordered by bib
+pub fn archers_of(
+    connection: &mut DbConnection,
+    tournament_id: i32,
+) -> Result<Vec<crate::models::Archer>> {
+    use crate::schema::{archers, registrations};
+    Ok(archers::table
+        .inner_join(registrations::table)
+        .filter(registrations::tournament_id.eq(tournament_id))
+        .order(archers::bib)
+        .select(crate::models::Archer::as_select())
+        .load(connection)?)
+}
+
+pub struct DeletedArcher {
+    pub archer: crate::models::Archer,
+    /// Members left in the archer's team. Empty teams are removed.
+    pub team_members_left: Option<i64>,
+}
+
+/// Removes an archer. Its team and registration are removed as well once they have no archers left.
+/// Archers of archived tournaments are kept.
+pub fn delete_archer(connection: &mut DbConnection, bib: i32) -> Result<Option<DeletedArcher>> {
+    use crate::schema::{archers, registrations, teams};
+    connection.transaction(|conn| {
+        crate::archive::refuse_archived_archer(conn, bib)?;
+        let Some(archer) = archers::table
+            .find(bib)
+            .select(crate::models::Archer::as_select())
+            .first(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        diesel::delete(archers::table.find(bib)).execute(conn)?;
+        let team_members_left = match archer.team_id {
+            Some(team_id) => {
+                let members: i64 = archers::table
+                    .filter(archers::team_id.eq(team_id))
+                    .count()
+                    .get_result(conn)?;
+                if members == 0 {
+                    diesel::delete(teams::table.find(team_id)).execute(conn)?;
+                }
+                Some(members)
+            }
+            None => None,
+        };
+        let archers_left: i64 = archers::table
+            .filter(archers::registration_id.eq(archer.registration_id))
+            .count()
+            .get_result(conn)?;
+        if archers_left == 0 {
+            diesel::delete(registrations::table.find(archer.registration_id)).execute(conn)?;
+        }
+        Ok(Some(DeletedArcher {
+            archer,
+            team_members_left,
+        }))
+    })
 }
 
-fn get_archers() -> Result<Vec<RegisteredArcher>> {
-    use crate::models::*;
-    use crate::schema::archers::dsl::*;
-    let mut connection = crate::db::establish_connection();
-    let ret = archers.load::<Archer>(&mut connection)?;
+/// Registration with its archers and teams as stored
+struct StoredRegistration {
+    registration: crate::models::Registration,
+    tournament: Tournament,
+    archers: Vec<crate::models::Archer>,
+    /// Id, name and class code
+    teams: Vec<(i32, String, String)>,
+}
 
-    Ok(ret.into_iter().map(|a| a.into()).collect())
+fn stored_registration(
+    connection: &mut DbConnection,
+    bib: i32,
+) -> Result<Option<StoredRegistration>> {
+    use crate::schema::{archers, registrations, teams};
+    let Some(registration_id) = archers::table
+        .find(bib)
+        .select(archers::registration_id)
+        .first::<i32>(connection)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let registration = registrations::table
+        .find(registration_id)
+        .select(crate::models::Registration::as_select())
+        .first(connection)?;
+    Ok(Some(StoredRegistration {
+        tournament: crate::tournament::by_id(connection, registration.tournament_id)?,
+        archers: archers::table
+            .filter(archers::registration_id.eq(registration_id))
+            .order(archers::bib)
+            .select(crate::models::Archer::as_select())
+            .load(connection)?,
+        teams: teams::table
+            .filter(teams::registration_id.eq(registration_id))
+            .order(teams::id)
+            .select((teams::id, teams::name, teams::class))
+            .load(connection)?,
+        registration,
+    }))
+}
+
+/// Sends the registration mail again to the registrator of the archer.
+/// Returns false if there is no archer with the bib.
+pub async fn resend_registration_mail(state: &AppState, bib: i32) -> Result<bool> {
+    let Some(stored) = db::run(&state.pool, move |conn| stored_registration(conn, bib)).await?
+    else {
+        return Ok(false);
+    };
+    let Some(mail_address) = stored.registration.email else {
+        return Err(Error::DataError(format!(
+            "Registration of bib {} is anonymized",
+            bib
+        )));
+    };
+    let rules = &state.rules;
+    let tournament = &stored.tournament;
+    let locale = db::parse_enum_code(&stored.registration.locale).unwrap_or_default();
+    let teams: Vec<EmailTeam> = stored
+        .teams
+        .iter()
+        .map(|(id, name, class)| {
+            let cls = rules.team_class(class);
+            EmailTeam {
+                name: name.clone(),
+                class: cls.map_or(class.clone(), |cls| cls.name(locale).into()),
+                members: stored
+                    .archers
+                    .iter()
+                    .filter(|archer| archer.team_id == Some(*id))
+                    .map(|archer| format!("{} {}", archer.first_name, archer.last_name))
+                    .collect(),
+                price: format_price(cls.map_or(0, |cls| tournament.team_price(cls))),
+            }
+        })
+        .collect();
+    let total_price: u32 = stored
+        .archers
+        .iter()
+        .filter_map(|archer| rules.class_from_code(&archer.class).ok())
+        .map(|cls| tournament.price(cls))
+        .sum::<u32>()
+        + stored
+            .teams
+            .iter()
+            .filter_map(|(_, _, class)| rules.team_class(class))
+            .map(|cls| tournament.team_price(cls))
+            .sum::<u32>();
+    let mail_data = EmailData {
+        tournament: MailTournament::new(tournament, locale),
+        comment: stored.registration.comment,
+        club: stored.registration.club,
+        mail_address,
+        name: stored.registration.name,
+        archers: stored
+            .archers
+            .iter()
+            .map(|archer| EmailArcher::from_stored(archer, locale, rules, tournament))
+            .collect(),
+        teams,
+        total_price: format_price(total_price),
+        payment: state.config.payment.clone(),
+    };
+    send_registration_mail(state, mail_data, locale).await?;
+    Ok(true)
 }
 
 impl From<crate::models::Archer> for RegisteredArcher {
@@ -178,21 +652,35 @@ impl From<crate::models::Archer> for RegisteredArcher {
             first_name: val.first_name,
             last_name: val.last_name,
             class: val.class,
-            divison: val.division,
-            session: val.session as u8,
-            club: val.country_name,
+            divison: db::parse_enum_code::<common::bow_type::BowType>(&val.bow_type)
+                .map_or("", |bow_type| bow_type.ianseo_division())
+                .to_string(),
+            // Numbered from 1 like in Ianseo
+            session: val.session as u8 + 1,
+            club: val.club_name,
         }
     }
 }
 
 #[derive(Debug, serde::Serialize)]
 struct EmailData {
+    tournament: MailTournament,
     comment: String,
     club: String,
     mail_address: String,
     name: String,
     archers: Vec<EmailArcher>,
+    teams: Vec<EmailTeam>,
     total_price: String,
+    payment: crate::config::PaymentConfig,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct EmailTeam {
+    name: String,
+    class: String,
+    members: Vec<String>,
+    price: String,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -205,57 +693,333 @@ struct EmailArcher {
     target: String,
     date_of_birth: String,
     price: String,
+    finals: String,
 }
 
 impl EmailArcher {
-    fn from(val: &common::archer::Archer, locale: common::locale::Locale) -> Self {
-        use Class::*;
+    fn from(
+        val: &common::archer::Archer,
+        locale: common::locale::Locale,
+        rules: &Rules,
+        tournament: &Tournament,
+    ) -> Self {
+        let cls = rules.class(val.class());
         EmailArcher {
             first_name: val.first_name.clone(),
             last_name: val.last_name.clone(),
-            session: match val.session {
-                0 => match locale {
-                    common::locale::Locale::En => "Morning",
-                    common::locale::Locale::De => "Vormittag",
-                }
-                .into(),
-                1 => match locale {
-                    common::locale::Locale::En => "Afternoon",
-                    common::locale::Locale::De => "Nachmittag",
-                }
-                .into(),
-                2 => match locale {
-                    common::locale::Locale::En => "waiting list - morning only",
-                    common::locale::Locale::De => "Warteliste - nur Vormittags",
-                }
-                .into(),
-                3 => match locale {
-                    common::locale::Locale::En => "waiting list - afternoon only",
-                    common::locale::Locale::De => "Warteliste - nur Nachmittags",
-                }
-                .into(),
-                _ => format!("{}", val.session),
-            },
-            class: val.class().name(locale).into(),
-            division: match val.class() {
-                RUE20M | RUE20W | RU15M | RU15W | RU13M | RU13W | RU11M | RU11W | RU18M | RU18W
-                | RU21M | RU21W | RUE49M | RUE49W | RUE65M | RUE65W => "Recurve",
-
-                BUE20M | BUE20W | BU15 | BU21 => match locale {
-                    common::locale::Locale::En => "Barebow",
-                    common::locale::Locale::De => "Blank",
-                },
-
-                CUE20M | CUE20W | CU15 | CU21 | CUE49M | CUE49W | CUE65M | CUE65W => "Compound",
-            }
-            .into(),
+            session: session_name(val.session, locale, tournament),
+            class: cls.map_or(val.class().to_string(), |cls| cls.name(locale).into()),
+            division: cls.map_or("", |cls| cls.bow_type.name(locale)).into(),
             target: val.target_face().to_string(),
             date_of_birth: val.date_of_birth().format("%Y-%m-%d").to_string(),
-            price: format!(
-                "{},{:02}€",
-                val.class().price() / 100,
-                val.class().price() % 100
+            price: format_price(price(val, rules, tournament)),
+            finals: finals_names(val.finals(), locale),
+        }
+    }
+
+    fn from_stored(
+        val: &crate::models::Archer,
+        locale: common::locale::Locale,
+        rules: &Rules,
+        tournament: &Tournament,
+    ) -> Self {
+        let cls = rules.class_from_code(&val.class).ok();
+        EmailArcher {
+            first_name: val.first_name.clone(),
+            last_name: val.last_name.clone(),
+            session: session_name(val.session as u8, locale, tournament),
+            class: cls.map_or(val.class.clone(), |cls| cls.name(locale).into()),
+            division: cls.map_or("", |cls| cls.bow_type.name(locale)).into(),
+            target: TargetFace::from_str(&val.target_face)
+                .map_or(val.target_face.clone(), |tf| tf.to_string()),
+            date_of_birth: val.date_of_birth.format("%Y-%m-%d").to_string(),
+            price: format_price(cls.map_or(0, |cls| tournament.price(cls))),
+            finals: finals_names(
+                Finals {
+                    individual: val.individual_final,
+                    team: val.team_final,
+                    mixed_team: val.mixed_team_final,
+                },
+                locale,
             ),
         }
     }
 }
+
+/// Name of the session for the mails. Every session has a waiting list following the sessions.
+fn session_name(session: u8, locale: common::locale::Locale, tournament: &Tournament) -> String {
+    let sessions = &tournament.sessions;
+    let session = usize::from(session);
+    if let Some(name) = sessions.get(session) {
+        name.get(locale).into()
+    } else if let Some(name) = session
+        .checked_sub(sessions.len())
+        .and_then(|waiting_list| sessions.get(waiting_list))
+    {
+        match locale {
+            common::locale::Locale::En => format!("waiting list - {} only", name.get(locale)),
+            common::locale::Locale::De => format!("Warteliste - nur {}", name.get(locale)),
+        }
+    } else {
+        format!("{}", session)
+    }
+}
+
+/// Finals an archer signed up for, listed for the mails
+fn finals_names(finals: Finals, locale: common::locale::Locale) -> String {
+    use common::locale::Locale;
+    let names: Vec<_> = [
+        (finals.individual, "Einzel", "individual"),
+        (finals.team, "Mannschaft", "team"),
+        (finals.mixed_team, "Mixed", "mixed team"),
+    ]
+    .into_iter()
+    .filter(|(chosen, _, _)| *chosen)
+    .map(|(_, de, en)| match locale {
+        Locale::En => en,
+        Locale::De => de,
+    })
+    .collect();
+    if names.is_empty() {
+        match locale {
+            Locale::En => "none",
+            Locale::De => "keine",
+        }
+        .into()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[test]
+fn test_save_archers_of_team() {
+    let Some(mut connection) = crate::db::test_connection() else {
+        return;
+    };
+    let rules = Rules::default();
+    let tournament = Tournament {
+        season_start: chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+        ..Default::default()
+    };
+    let archer = |first_name: &str| {
+        Archer::new(
+            first_name.into(),
+            "Bar".into(),
+            email_address::EmailAddress::from_str("foo@bar.com").unwrap(),
+            chrono::NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            common::class::Class::new("RUE20M"),
+            common::target_face::TargetFace::M18Spot,
+            String::new(),
+            "PSV".into(),
+            0,
+            None,
+            Finals {
+                team: true,
+                ..Default::default()
+            },
+            &rules,
+            &tournament,
+        )
+        .unwrap()
+    };
+    let registration_id = save_registration(
+        &mut connection,
+        &crate::models::InsertableRegistration {
+            tournament_id: 1,
+            name: "Foo Bar".into(),
+            email: "foo@bar.com".into(),
+            club: "PSV".into(),
+            comment: String::new(),
+            locale: db::enum_code(&common::locale::Locale::De),
+            created_at: db::now(),
+        },
+    )
+    .unwrap();
+    let bibs: Vec<i32> = ["A", "B", "C"]
+        .into_iter()
+        .map(|name| {
+            save_archer(
+                &mut connection,
+                archer(name),
+                true,
+                registration_id,
+                &[],
+                &rules,
+            )
+            .unwrap()
+        })
+        .collect();
+    let team = Team {
+        name: "PSV 1".into(),
+        members: vec![0, 1, 2],
+    };
+    save_team(
+        &mut connection,
+        team,
+        rules.team_class("RT").unwrap(),
+        &bibs,
+        registration_id,
+    )
+    .unwrap();
+    let team_ids: Vec<Option<i32>> = schema::archers::table
+        .filter(schema::archers::registration_id.eq(registration_id))
+        .select(schema::archers::team_id)
+        .load(&mut connection)
+        .unwrap();
+    assert_eq!(team_ids.len(), 3);
+    assert!(team_ids.iter().all(|id| id.is_some() && *id == team_ids[0]));
+    let archers = get_archers(&mut connection, 1).unwrap();
+    assert_eq!(archers.len(), 3);
+    assert_eq!(archers[0].class, "RUE20M");
+    assert_eq!(archers[0].divison, "R");
+    assert_eq!(archers[0].session, 1);
+
+    let deleted = delete_archer(&mut connection, bibs[0]).unwrap().unwrap();
+    assert_eq!(deleted.archer.first_name, "A");
+    assert_eq!(deleted.team_members_left, Some(2));
+    for &bib in &bibs[1..] {
+        delete_archer(&mut connection, bib).unwrap();
+    }
+    assert!(delete_archer(&mut connection, bibs[0]).unwrap().is_none());
+    assert_eq!(
+        schema::teams::table
+            .count()
+            .get_result::<i64>(&mut connection)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        schema::registrations::table
+            .count()
+            .get_result::<i64>(&mut connection)
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn test_session_names() {
+    use common::locale::Locale;
+    let tournament = Tournament::default();
+    assert_eq!(session_name(1, Locale::De, &tournament), "Nachmittag");
+    assert_eq!(
+        session_name(2, Locale::En, &tournament),
+        "waiting list - Morning only"
+    );
+    assert_eq!(
+        session_name(3, Locale::De, &tournament),
+        "Warteliste - nur Nachmittag"
+    );
+    assert_eq!(session_name(4, Locale::De, &tournament), "4");
+}
+
+/// Registration of the archers, as sent by the form
+#[cfg(all(test, feature = "sqlite"))]
+pub fn test_registration(archers: Vec<serde_json::Value>) -> CreateArchersPayload {
+    serde_json::from_value(serde_json::json!({
+        "name": "Max Mustermann",
+        "mail": "max@example.com",
+        "comment": "",
+        "club": "PSV",
+        "archers": archers,
+    }))
+    .unwrap()
+}
+
+/// Archer as sent by the form, with the first target face of the class
+#[cfg(all(test, feature = "sqlite"))]
+pub fn test_archer(
+    first_name: &str,
+    class: &str,
+    date_of_birth: &str,
+    gender: Option<common::gender::Gender>,
+) -> serde_json::Value {
+    let class = common::class::Class::new(class);
+    serde_json::json!({
+        "first_name": first_name,
+        "last_name": "Mustermann",
+        "mail": "max@example.com",
+        "comment": "",
+        "club": "PSV",
+        "session": 0,
+        "date_of_birth": date_of_birth,
+        "class": class,
+        "target_face": Rules::default().target_faces(&class, &Tournament::default()).first(),
+        "gender": gender,
+    })
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+async fn post_archers(
+    state: &AppState,
+    current: &CurrentTournament,
+    payload: CreateArchersPayload,
+) -> StatusCode {
+    let payload = CreateArchersPayload {
+        form_stamp: state.form_signer.issue(),
+        ..payload
+    };
+    create_archers(State(state.clone()), current.clone(), Json(payload))
+        .await
+        .unwrap()
+        .into_response()
+        .status()
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_gender_required_for_classes_of_any_gender() {
+    use common::gender::Gender;
+    let (state, path) = crate::app::test_state("gender", crate::app::test_config());
+    let (id, tournament) = crate::tournament::find(&mut state.pool.get().unwrap(), "indoor25")
+        .unwrap()
+        .unwrap();
+    let current = CurrentTournament { id, tournament };
+    let registration =
+        |gender| test_registration(vec![test_archer("Max", "BU15", "2013-05-03", gender)]);
+    assert_eq!(
+        post_archers(&state, &current, registration(None)).await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+    assert_eq!(
+        post_archers(&state, &current, registration(Some(Gender::Male))).await,
+        StatusCode::CREATED
+    );
+    let stored: Option<String> = schema::archers::table
+        .select(schema::archers::gender)
+        .first(&mut state.pool.get().unwrap())
+        .unwrap();
+    assert_eq!(stored.as_deref(), Some("Male"));
+    drop(state);
+    crate::app::remove_test_database(&path);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_class_and_session_checked() {
+    let (state, path) = crate::app::test_state("class", crate::app::test_config());
+    let (id, tournament) = crate::tournament::find(&mut state.pool.get().unwrap(), "indoor25")
+        .unwrap()
+        .unwrap();
+    let current = CurrentTournament { id, tournament };
+    // Too old for the youth class
+    let adult = test_archer("Max", "RU13M", "1990-05-03", None);
+    assert_eq!(
+        post_archers(&state, &current, test_registration(vec![adult])).await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+    let mut waiting_list = test_archer("Max", "RUE20M", "1990-05-03", None);
+    waiting_list["session"] = 3.into();
+    let mut unknown_session = test_archer("Moritz", "RUE20M", "1990-05-03", None);
+    unknown_session["session"] = 4.into();
+    assert_eq!(
+        post_archers(&state, &current, test_registration(vec![unknown_session])).await,
+        StatusCode::UNPROCESSABLE_ENTITY
+    );
+    assert_eq!(
+        post_archers(&state, &current, test_registration(vec![waiting_list])).await,
+        StatusCode::CREATED
+    );
+    drop(state);
+    crate::app::remove_test_database(&path);
+}
@@ -0,0 +1,441 @@
+use crate::{config::ArchiveConfig, db::DbConnection, error::*, models, schema};
+use common::bow_type::BowType;
+use common::tournament::Tournament;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Freezes a finished tournament and exports its lists into the archive directory.
+/// The archers stay in the database, so they can be offered for registering again.
+/// A tournament is only archived once.
+pub fn archive(
+    connection: &mut DbConnection,
+    id: i32,
+    tournament: &Tournament,
+    config: &ArchiveConfig,
+) -> std::result::Result<PathBuf, Box<dyn std::error::Error>> {
+    use schema::{pending_registrations, tournaments};
+    let now = crate::db::now();
+    let dropped = connection.transaction(|conn| -> Result<Option<usize>> {
+        let archived = diesel::update(
+            tournaments::table
+                .find(id)
+                .filter(tournaments::archived_at.is_null()),
+        )
+        .set(tournaments::archived_at.eq(now))
+        .execute(conn)?;
+        if archived == 0 {
+            return Ok(None);
+        }
+        Ok(Some(
+            diesel::delete(
+                pending_registrations::table.filter(pending_registrations::tournament_id.eq(id)),
+            )
+            .execute(conn)?,
+        ))
+    })?;
+    let Some(dropped) = dropped else {
+        return Err(format!("{} is archived already", tournament.slug).into());
+    };
+    if dropped > 0 {
+        log::info!("Dropped {} unconfirmed registrations", dropped);
+    }
+
+    let directory = config.directory.join(&tournament.slug);
+    std::fs::create_dir_all(&directory)?;
+    let archived = Tournament {
+        archived_at: Some(now),
+        ..tournament.clone()
+    };
+    std::fs::write(
+        directory.join("tournament.json"),
+        serde_json::to_string_pretty(&archived)?,
+    )?;
+    export_lists(connection, id, &directory)?;
+    Ok(directory)
+}
+
+/// Fails with [`Error::ArchivedError`] if the tournament with the slug is archived
+pub fn refuse_archived_tournament(connection: &mut DbConnection, slug: &str) -> Result<()> {
+    use schema::tournaments;
+    let archived = tournaments::table
+        .filter(tournaments::slug.eq(slug))
+        .select(tournaments::archived_at.is_not_null())
+        .first(connection)
+        .optional()?;
+    refuse_archived(archived)
+}
+
+/// Fails with [`Error::ArchivedError`] if the tournament of the archer is archived
+pub fn refuse_archived_archer(connection: &mut DbConnection, bib: i32) -> Result<()> {
+    use schema::{archers, registrations, tournaments};
+    let archived = archers::table
+        .inner_join(registrations::table.inner_join(tournaments::table))
+        .filter(archers::bib.eq(bib))
+        .select(tournaments::archived_at.is_not_null())
+        .first(connection)
+        .optional()?;
+    refuse_archived(archived)
+}
+
+fn refuse_archived(archived: Option<bool>) -> Result<()> {
+    match archived {
+        Some(true) => Err(Error::ArchivedError),
+        _ => Ok(()),
+    }
+}
+
+/// Writes the archer and team lists of the tournament into the directory
+fn export_lists(
+    connection: &mut DbConnection,
+    id: i32,
+    directory: &Path,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_path(directory.join("archers.csv"))?;
+    for archer in exported_archers(connection, id)? {
+        writer.serialize(archer)?;
+    }
+    writer.flush()?;
+    let mut writer = csv::Writer::from_path(directory.join("teams.csv"))?;
+    for team in exported_teams(connection, id)? {
+        writer.serialize(team)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Creates the next edition of a tournament with the same settings and shifted dates
+pub fn rollover(
+    connection: &mut DbConnection,
+    tournament: &Tournament,
+    slug: String,
+    years: i32,
+) -> Result<Tournament> {
+    let next = tournament.next_edition(slug, years);
+    crate::tournament::save(connection, &next)?;
+    Ok(next)
+}
+
+/// Personal data removed by [`anonymize`]
+pub struct Anonymized {
+    pub archers: usize,
+    /// Slugs of the expired tournaments whose exported lists were rewritten
+    pub tournaments: Vec<String>,
+    pub queued_mails: usize,
+    pub prefill_tokens: usize,
+}
+
+/// Removes the personal data of archived tournaments whose retention period is over.
+/// Only the year of birth, the club and the class are kept for statistics, also in the exported
+/// lists. Queued mails and prefill links of addresses which aren't registered anymore are removed.
+pub fn anonymize(
+    connection: &mut DbConnection,
+    config: &ArchiveConfig,
+) -> std::result::Result<Anonymized, Box<dyn std::error::Error>> {
+    use chrono::Datelike;
+    use schema::{archers, mail_queue, prefill_tokens, registrations, tournaments};
+    let cutoff = crate::db::now() - chrono::Duration::days(config.retention_days.into());
+    let (archers, queued_mails, prefill_tokens) = connection.transaction(|conn| -> Result<_> {
+        let expired: Vec<(i32, Option<String>)> = registrations::table
+            .inner_join(tournaments::table)
+            .filter(tournaments::archived_at.lt(cutoff))
+            .filter(registrations::email.is_not_null())
+            .select((registrations::id, registrations::email))
+            .load(conn)?;
+        let ids: Vec<i32> = expired.iter().map(|(id, _)| *id).collect();
+        let archers: Vec<(i32, chrono::NaiveDate)> = archers::table
+            .filter(archers::registration_id.eq_any(&ids))
+            .select((archers::bib, archers::date_of_birth))
+            .load(conn)?;
+        for (bib, date_of_birth) in &archers {
+            diesel::update(archers::table.find(bib))
+                .set((
+                    archers::first_name.eq(""),
+                    archers::last_name.eq(""),
+                    archers::date_of_birth.eq(date_of_birth.with_ordinal(1).unwrap()),
+                ))
+                .execute(conn)?;
+        }
+        diesel::update(registrations::table.filter(registrations::id.eq_any(&ids)))
+            .set((
+                registrations::name.eq(""),
+                registrations::email.eq(None::<String>),
+                registrations::comment.eq(""),
+            ))
+            .execute(conn)?;
+
+        // Addresses still registered for another tournament keep their mails and links
+        let registered: BTreeSet<String> = registrations::table
+            .filter(registrations::email.is_not_null())
+            .select(crate::db::lower(registrations::email.assume_not_null()))
+            .load::<String>(conn)?
+            .into_iter()
+            .collect();
+        let forgotten: Vec<String> = expired
+            .iter()
+            .filter_map(|(_, email)| email.as_deref())
+            .map(str::to_lowercase)
+            .filter(|email| !registered.contains(email))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let queued_mails = diesel::delete(
+            mail_queue::table.filter(crate::db::lower(mail_queue::recipient).eq_any(&forgotten)),
+        )
+        .execute(conn)?;
+        let prefill_tokens = diesel::delete(
+            prefill_tokens::table
+                .filter(crate::db::lower(prefill_tokens::email).eq_any(&forgotten)),
+        )
+        .execute(conn)?;
+        Ok((archers.len(), queued_mails, prefill_tokens))
+    })?;
+
+    // The exported lists are written again from the anonymized archers. All expired tournaments
+    // are exported, in case a previous run failed after anonymizing.
+    let expired: Vec<(i32, String)> = tournaments::table
+        .filter(tournaments::archived_at.lt(cutoff))
+        .select((tournaments::id, tournaments::slug))
+        .load(connection)?;
+    let mut tournaments = Vec::new();
+    for (id, slug) in expired {
+        let directory = config.directory.join(&slug);
+        if directory.is_dir() {
+            export_lists(connection, id, &directory)?;
+            tournaments.push(slug);
+        }
+    }
+    Ok(Anonymized {
+        archers,
+        tournaments,
+        queued_mails,
+        prefill_tokens,
+    })
+}
+
+/// Row of the archived archer list
+#[derive(Serialize)]
+struct ExportedArcher {
+    bib: i32,
+    session: i32,
+    division: String,
+    class: String,
+    target_face: String,
+    first_name: String,
+    last_name: String,
+    date_of_birth: String,
+    club_code: String,
+    club: String,
+    email: String,
+    comment: String,
+    paid: bool,
+    team: String,
+    individual_final: bool,
+    team_final: bool,
+    mixed_team_final: bool,
+}
+
+/// Row of the archived team list
+#[derive(Serialize)]
+struct ExportedTeam {
+    name: String,
+    class: String,
+    email: String,
+    /// Bibs of the members, separated by spaces
+    members: String,
+}
+
+/// Archer, mail, comment and team name as loaded from the database
+type ExportColumns = (models::Archer, Option<String>, String, Option<String>);
+
+fn exported_archers(
+    connection: &mut DbConnection,
+    tournament_id: i32,
+) -> Result<Vec<ExportedArcher>> {
+    use schema::{archers, registrations, teams};
+    let rows: Vec<ExportColumns> = archers::table
+        .inner_join(registrations::table)
+        .left_join(teams::table)
+        .filter(registrations::tournament_id.eq(tournament_id))
+        .order(archers::bib)
+        .select((
+            models::Archer::as_select(),
+            registrations::email,
+            registrations::comment,
+            teams::name.nullable(),
+        ))
+        .load(connection)?;
+    Ok(rows
+        .into_iter()
+        .map(|(archer, email, comment, team)| ExportedArcher {
+            bib: archer.bib,
+            session: archer.session + 1,
+            division: crate::db::parse_enum_code::<BowType>(&archer.bow_type)
+                .map_or(archer.bow_type, |bow_type| {
+                    bow_type.ianseo_division().to_string()
+                }),
+            class: archer.class,
+            target_face: archer.target_face,
+            first_name: archer.first_name,
+            last_name: archer.last_name,
+            date_of_birth: archer.date_of_birth.format("%Y-%m-%d").to_string(),
+            club_code: archer.club_code.unwrap_or_default(),
+            club: archer.club_name,
+            email: email.unwrap_or_default(),
+            comment,
+            paid: archer.paid,
+            team: team.unwrap_or_default(),
+            individual_final: archer.individual_final,
+            team_final: archer.team_final,
+            mixed_team_final: archer.mixed_team_final,
+        })
+        .collect())
+}
+
+fn exported_teams(connection: &mut DbConnection, tournament_id: i32) -> Result<Vec<ExportedTeam>> {
+    use schema::{archers, registrations, teams};
+    let teams: Vec<(i32, String, String, Option<String>)> = teams::table
+        .inner_join(registrations::table)
+        .filter(registrations::tournament_id.eq(tournament_id))
+        .order(teams::id)
+        .select((teams::id, teams::name, teams::class, registrations::email))
+        .load(connection)?;
+    teams
+        .into_iter()
+        .map(|(id, name, class, email)| {
+            let members: Vec<i32> = archers::table
+                .filter(archers::team_id.eq(id))
+                .order(archers::bib)
+                .select(archers::bib)
+                .load(connection)?;
+            Ok(ExportedTeam {
+                name,
+                class,
+                email: email.unwrap_or_default(),
+                members: members
+                    .iter()
+                    .map(i32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_archive_and_anonymize() {
+    use schema::{archers, mail_queue, prefill_tokens, registrations, tournaments};
+    let Some(mut connection) = crate::db::test_connection() else {
+        return;
+    };
+    let directory = std::env::temp_dir().join(format!("archive-test-{}", std::process::id()));
+    let config = ArchiveConfig {
+        directory: directory.clone(),
+        retention_days: 30,
+    };
+    let tournament = Tournament {
+        slug: "old".into(),
+        ..Default::default()
+    };
+    crate::tournament::save(&mut connection, &tournament).unwrap();
+    let (id, tournament) = crate::tournament::find(&mut connection, "old")
+        .unwrap()
+        .unwrap();
+    let registration_id: i32 = diesel::insert_into(registrations::table)
+        .values(models::InsertableRegistration {
+            tournament_id: id,
+            name: "Max".into(),
+            email: "Max@example.com".into(),
+            club: "PSV".into(),
+            comment: String::new(),
+            locale: "De".into(),
+            created_at: crate::db::now(),
+        })
+        .returning(registrations::id)
+        .get_result(&mut connection)
+        .unwrap();
+    diesel::insert_into(archers::table)
+        .values(models::InsertableArcher {
+            registration_id,
+            session: 0,
+            first_name: "Max".into(),
+            last_name: "Mustermann".into(),
+            date_of_birth: chrono::NaiveDate::from_ymd_opt(1990, 5, 3).unwrap(),
+            gender: None,
+            bow_type: "Recurve".into(),
+            class: "RUE20M".into(),
+            target_face: "M18cm40".into(),
+            club_code: None,
+            club_name: "PSV".into(),
+            individual_final: false,
+            team_final: false,
+            mixed_team_final: false,
+        })
+        .execute(&mut connection)
+        .unwrap();
+    diesel::insert_into(mail_queue::table)
+        .values(models::InsertableQueuedMail {
+            recipient: "max@example.com".into(),
+            subject: "Info".into(),
+            body: "Hallo Max".into(),
+            created_at: crate::db::now(),
+        })
+        .execute(&mut connection)
+        .unwrap();
+    diesel::insert_into(prefill_tokens::table)
+        .values(models::PrefillToken {
+            token: "token".into(),
+            email: "max@example.com".into(),
+            created_at: crate::db::now(),
+        })
+        .execute(&mut connection)
+        .unwrap();
+
+    archive(&mut connection, id, &tournament, &config).unwrap();
+    assert!(archive(&mut connection, id, &tournament, &config).is_err());
+    let bib: i32 = archers::table
+        .select(archers::bib)
+        .first(&mut connection)
+        .unwrap();
+    let edited = Tournament {
+        name: "Renamed".into(),
+        ..tournament.clone()
+    };
+    assert!(matches!(
+        crate::tournament::save(&mut connection, &edited),
+        Err(Error::ArchivedError)
+    ));
+    assert!(matches!(
+        crate::archer::delete_archer(&mut connection, bib),
+        Err(Error::ArchivedError)
+    ));
+    assert_eq!(
+        crate::tournament::find(&mut connection, "old")
+            .unwrap()
+            .unwrap()
+            .1
+            .name,
+        tournament.name
+    );
+    let archers_csv = directory.join("old").join("archers.csv");
+    assert!(std::fs::read_to_string(&archers_csv)
+        .unwrap()
+        .contains("Mustermann"));
+
+    // Nothing to anonymize within the retention period
+    assert_eq!(anonymize(&mut connection, &config).unwrap().archers, 0);
+    diesel::update(tournaments::table.find(id))
+        .set(tournaments::archived_at.eq(crate::db::now() - chrono::Duration::days(31)))
+        .execute(&mut connection)
+        .unwrap();
+    let anonymized = anonymize(&mut connection, &config).unwrap();
+    assert_eq!(anonymized.archers, 1);
+    assert_eq!(anonymized.tournaments, ["old"]);
+    assert_eq!(anonymized.queued_mails, 1);
+    assert_eq!(anonymized.prefill_tokens, 1);
+    let exported = std::fs::read_to_string(&archers_csv).unwrap();
+    assert!(!exported.contains("Mustermann") && !exported.contains("example.com"));
+    assert!(exported.contains("1990-01-01"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,308 @@
+use crate::app::AppState;
+use crate::config::BackupConfig;
+use crate::db;
+use diesel::prelude::*;
+use diesel::SqliteConnection;
+use libsqlite3_sys as ffi;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+const FILE_PREFIX: &str = "backup-";
+const FILE_SUFFIX: &str = ".sqlite";
+
+/// Backup errors are only logged or printed, so any error is boxed
+type BackupError = Box<dyn std::error::Error + Send + Sync>;
+type BackupResult<T> = std::result::Result<T, BackupError>;
+
+/// Pages copied at once. Writers are only blocked while a step runs.
+const PAGES_PER_STEP: i32 = 256;
+/// Pause between the steps of a backup and before retrying a locked database
+const STEP_PAUSE: Duration = Duration::from_millis(20);
+
+/// Backs up the database in the interval of the current config, starting right away.
+/// A reloaded config applies to the next backup, it may also turn the backups on or off.
+pub async fn schedule(database: PathBuf, mut states: watch::Receiver<AppState>) {
+    let mut last_backup = None;
+    loop {
+        let config = states.borrow_and_update().config.backup.clone();
+        let Some(config) = config else {
+            if states.changed().await.is_err() {
+                return;
+            }
+            continue;
+        };
+        let due = match last_backup {
+            Some(last) => last + Duration::from_secs(u64::from(config.interval_hours) * 3600),
+            None => Instant::now(),
+        };
+        if due > Instant::now() {
+            tokio::select! {
+                _ = tokio::time::sleep_until(due) => (),
+                changed = states.changed() => if changed.is_err() {
+                    return;
+                },
+            }
+            continue;
+        }
+        last_backup = Some(Instant::now());
+        let database_file = database.clone();
+        match tokio::task::spawn_blocking(move || backup(&database_file, &config))
+            .await
+            .unwrap()
+        {
+            Ok(path) => log::info!("Backed up database to {:?}", path),
+            Err(e) => log::error!("Backup of the database failed: {}", e),
+        }
+    }
+}
+
+/// Copies the database into a new file of the backup directory, while the server keeps running.
+/// Only the newest backups are kept.
+pub fn backup(database: &Path, config: &BackupConfig) -> BackupResult<PathBuf> {
+    std::fs::create_dir_all(&config.directory)?;
+    let name = format!(
+        "{}{}{}",
+        FILE_PREFIX,
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"),
+        FILE_SUFFIX
+    );
+    let path = config.directory.join(name);
+    // Rotation must never pick up a half written backup
+    let partial = path.with_extension("partial");
+    copy_database(database, &partial)?;
+    std::fs::rename(&partial, &path)?;
+    for old in rotated_backups(&config.directory, config.keep)? {
+        std::fs::remove_file(&old)?;
+        log::info!("Removed old backup {:?}", old);
+    }
+    Ok(path)
+}
+
+/// Backups of the directory beyond the newest `keep` ones
+fn rotated_backups(directory: &Path, keep: usize) -> std::io::Result<Vec<PathBuf>> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+        })
+        .collect();
+    // The timestamp in the name sorts oldest first
+    backups.sort();
+    let rotated = backups.len().saturating_sub(keep);
+    backups.truncate(rotated);
+    Ok(backups)
+}
+
+/// Replaces the database with a backup and migrates it. Returns the applied migrations.
+/// The server must be stopped, a database still used by other connections is refused.
+/// Backups of a newer schema than this build knows are refused as well.
+pub fn restore(database: &Path, backup: &Path) -> BackupResult<Vec<String>> {
+    verify(backup)?;
+    {
+        let destination = RawDatabase::open(
+            database,
+            ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+        )?;
+        destination.lock_exclusively()?;
+        copy(
+            &RawDatabase::open(backup, ffi::SQLITE_OPEN_READONLY)?,
+            &destination,
+        )?;
+    }
+    // The backup may have an older schema. Connections opened before the copy don't see it.
+    let mut connection =
+        SqliteConnection::establish(database.to_str().ok_or("Path isn't valid unicode")?)?;
+    db::migrate(&mut connection)
+}
+
+/// Checks the backup is an intact registration database this build can migrate
+fn verify(backup: &Path) -> BackupResult<()> {
+    use diesel::migration::MigrationSource;
+
+    #[derive(QueryableByName)]
+    struct Check {
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        quick_check: String,
+    }
+    #[derive(QueryableByName)]
+    struct Version {
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        version: String,
+    }
+
+    if !backup.is_file() {
+        return Err(format!("Backup {:?} doesn't exist", backup).into());
+    }
+    let mut connection =
+        SqliteConnection::establish(&format!("file:{}?mode=ro", backup.display()))?;
+    let check = diesel::sql_query("PRAGMA quick_check").get_result::<Check>(&mut connection)?;
+    if check.quick_check != "ok" {
+        return Err(format!("Backup is damaged: {}", check.quick_check).into());
+    }
+    let applied = diesel::sql_query("SELECT version FROM __diesel_schema_migrations")
+        .load::<Version>(&mut connection)
+        .map_err(|_| "Backup is no registration database")?;
+    let known: Vec<String> =
+        MigrationSource::<diesel::sqlite::Sqlite>::migrations(&db::MIGRATIONS)?
+            .iter()
+            .map(|migration| migration.name().version().to_string())
+            .collect();
+    if let Some(unknown) = applied.iter().find(|v| !known.contains(&v.version)) {
+        return Err(format!(
+            "Backup has the unknown migration {}, it was made by a newer version",
+            unknown.version
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Copies a database with the SQLite online backup API, which is consistent even while
+/// other connections write to the source
+fn copy_database(from: &Path, to: &Path) -> BackupResult<()> {
+    copy(
+        &RawDatabase::open(from, ffi::SQLITE_OPEN_READONLY)?,
+        &RawDatabase::open(to, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE)?,
+    )
+}
+
+fn copy(source: &RawDatabase, destination: &RawDatabase) -> BackupResult<()> {
+    // Safety: both handles stay open until the backup is finished
+    unsafe {
+        let backup =
+            ffi::sqlite3_backup_init(destination.0, c"main".as_ptr(), source.0, c"main".as_ptr());
+        if backup.is_null() {
+            return Err(sqlite_error(ffi::sqlite3_errcode(destination.0)));
+        }
+        let result = loop {
+            match ffi::sqlite3_backup_step(backup, PAGES_PER_STEP) {
+                ffi::SQLITE_DONE => break ffi::SQLITE_OK,
+                ffi::SQLITE_OK | ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED => {
+                    std::thread::sleep(STEP_PAUSE)
+                }
+                error => break error,
+            }
+        };
+        ffi::sqlite3_backup_finish(backup);
+        if result != ffi::SQLITE_OK {
+            return Err(sqlite_error(result));
+        }
+    }
+    Ok(())
+}
+
+fn sqlite_error(code: std::os::raw::c_int) -> BackupError {
+    Box::new(ffi::Error::new(code))
+}
+
+/// Connection handle of the SQLite C api, closed on drop
+struct RawDatabase(*mut ffi::sqlite3);
+
+impl RawDatabase {
+    fn open(path: &Path, flags: std::os::raw::c_int) -> BackupResult<Self> {
+        let path = std::ffi::CString::new(path.to_str().ok_or("Path isn't valid unicode")?)?;
+        let mut handle = std::ptr::null_mut();
+        // Safety: the handle is closed by drop, even if opening failed
+        let result =
+            unsafe { ffi::sqlite3_open_v2(path.as_ptr(), &mut handle, flags, std::ptr::null()) };
+        let database = RawDatabase(handle);
+        if result != ffi::SQLITE_OK {
+            return Err(sqlite_error(result));
+        }
+        Ok(database)
+    }
+
+    /// Keeps every other connection out until this one is closed.
+    /// Fails right away if another connection, like the one of a running server, uses the database.
+    fn lock_exclusively(&self) -> BackupResult<()> {
+        let sql = c"PRAGMA locking_mode = EXCLUSIVE; BEGIN EXCLUSIVE; COMMIT;";
+        // Safety: the handle is open and the statements are a nul terminated string
+        let result = unsafe {
+            ffi::sqlite3_exec(
+                self.0,
+                sql.as_ptr(),
+                None,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        match result {
+            ffi::SQLITE_OK => Ok(()),
+            ffi::SQLITE_BUSY => Err("Database is in use, stop the server first".into()),
+            error => Err(sqlite_error(error)),
+        }
+    }
+}
+
+impl Drop for RawDatabase {
+    fn drop(&mut self) {
+        // Safety: the handle came from sqlite3_open_v2 and is closed only here
+        unsafe {
+            ffi::sqlite3_close(self.0);
+        }
+    }
+}
+
+#[test]
+fn test_backup_and_restore() {
+    let directory = std::env::temp_dir().join(format!("backup-test-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let database = directory.join("db.sqlite");
+    let mut connection = SqliteConnection::establish(database.to_str().unwrap()).unwrap();
+    // Like the server's connections
+    diesel::sql_query("PRAGMA journal_mode = WAL")
+        .execute(&mut connection)
+        .unwrap();
+    db::migrate(&mut connection).unwrap();
+    diesel::sql_query("INSERT INTO clubs (code, name) VALUES ('1', 'PSV')")
+        .execute(&mut connection)
+        .unwrap();
+
+    let config = BackupConfig {
+        directory: directory.join("backups"),
+        interval_hours: 24,
+        keep: 2,
+    };
+    // Names are only unique per second, so older backups are faked
+    std::fs::create_dir_all(&config.directory).unwrap();
+    for old in ["2000-01-01_00-00-00", "2000-01-02_00-00-00"] {
+        std::fs::write(
+            config
+                .directory
+                .join(format!("{}{}{}", FILE_PREFIX, old, FILE_SUFFIX)),
+            "",
+        )
+        .unwrap();
+    }
+    let path = backup(&database, &config).unwrap();
+    let mut kept: Vec<_> = std::fs::read_dir(&config.directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    kept.sort();
+    assert_eq!(kept.len(), 2);
+    assert_eq!(kept[1], path);
+
+    diesel::sql_query("DELETE FROM clubs")
+        .execute(&mut connection)
+        .unwrap();
+    drop(connection);
+    // Refused while another connection, like the one of a server, uses the database
+    let mut held = SqliteConnection::establish(database.to_str().unwrap()).unwrap();
+    crate::club::all_clubs(&mut held).unwrap();
+    assert!(restore(&database, &path).is_err());
+    drop(held);
+    assert!(restore(&database, &path).unwrap().is_empty());
+    let mut connection = SqliteConnection::establish(database.to_str().unwrap()).unwrap();
+    let clubs = crate::club::all_clubs(&mut connection).unwrap();
+    assert_eq!(clubs.len(), 1);
+    drop(connection);
+    assert!(restore(&database, &kept[0]).is_err());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
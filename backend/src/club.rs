@@ -0,0 +1,107 @@
+use crate::{db, db::DbConnection, error::*, models, schema::clubs};
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use common::club::Club;
+use diesel::prelude::*;
+use serde::Deserialize;
+use std::path::Path;
+
+const MAX_SEARCH_RESULTS: i64 = 10;
+
+/// Inserts or updates all clubs of a CSV file with the columns `code` and `name`
+pub fn import_clubs(
+    connection: &mut DbConnection,
+    path: &Path,
+) -> std::result::Result<usize, Box<dyn std::error::Error>> {
+    // Later rows of the same code win
+    let mut clubs = std::collections::BTreeMap::new();
+    for club in csv::Reader::from_path(path)?.into_deserialize::<Club>() {
+        let club = club?;
+        let club = Club {
+            code: club.code.trim().to_string(),
+            name: club.name.trim().to_string(),
+        };
+        clubs.insert(
+            club.code.clone(),
+            models::Club {
+                search: club.search_text(),
+                code: club.code,
+                name: club.name,
+            },
+        );
+    }
+    Ok(connection.transaction(|conn| {
+        clubs.into_values().try_fold(0, |imported, club| {
+            diesel::insert_into(clubs::table)
+                .values(&club)
+                .on_conflict(clubs::code)
+                .do_update()
+                .set((clubs::name.eq(&club.name), clubs::search.eq(&club.search)))
+                .execute(conn)
+                .map(|inserted| imported + inserted)
+        })
+    })?)
+}
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    q: String,
+}
+
+pub async fn search_clubs(
+    State(pool): State<db::Pool>,
+    Query(params): Query<SearchParams>,
+) -> Result<impl IntoResponse> {
+    let words = Club::search_words(&params.q);
+    let clubs = db::run(&pool, move |conn| search(conn, &words)).await?;
+    Ok(Json(clubs))
+}
+
+/// Clubs containing all of the words in their search text, see [`Club::matches`]
+fn search(connection: &mut DbConnection, words: &[String]) -> Result<Vec<Club>> {
+    let mut query = clubs::table.into_boxed();
+    // Words only consist of letters and digits, so there is nothing to escape
+    for word in words {
+        query = query.filter(clubs::search.like(format!("%{}%", word)));
+    }
+    Ok(query
+        .order(clubs::name)
+        .limit(MAX_SEARCH_RESULTS)
+        .load::<models::Club>(connection)?
+        .into_iter()
+        .map(Club::from)
+        .collect())
+}
+
+pub fn all_clubs(connection: &mut DbConnection) -> Result<Vec<Club>> {
+    Ok(clubs::table
+        .order(clubs::name)
+        .load::<models::Club>(connection)?
+        .into_iter()
+        .map(Club::from)
+        .collect())
+}
+
+#[test]
+fn test_import_clubs_updates_names() {
+    let Some(mut connection) = crate::db::test_connection() else {
+        return;
+    };
+    let path = std::env::temp_dir().join(format!("clubs-test-{}.csv", std::process::id()));
+    std::fs::write(&path, "code,name\n001,PSV\n002,BSC\n002, BSC München \n").unwrap();
+    assert_eq!(import_clubs(&mut connection, &path).unwrap(), 2);
+    std::fs::write(&path, "code,name\n001,PSV München\n").unwrap();
+    import_clubs(&mut connection, &path).unwrap();
+    std::fs::remove_file(&path).ok();
+    let clubs = all_clubs(&mut connection).unwrap();
+    let names: Vec<_> = clubs.iter().map(|club| club.name.as_str()).collect();
+    assert_eq!(names, ["BSC München", "PSV München"]);
+    let mut found = |query: &str| search(&mut connection, &Club::search_words(query)).unwrap();
+    assert_eq!(found("muenchen psv"), [clubs[1].clone()]);
+    assert_eq!(found("").len(), 2);
+    assert!(found("augsburg").is_empty());
+}
@@ -1,19 +1,519 @@
 use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+/// Environment variables with this prefix override keys of the config file.
+/// Nested keys are separated by `__`, e.g. `PSV_MAIL_SERVER__SMTP_SERVER`.
+pub const ENV_PREFIX: &str = "PSV_";
+
+/// Sensitive keys. They are masked when printing the config and can be read from the file
+/// given by the key with the suffix `_file`, e.g. `admin_token_file`.
+const SECRETS: &[&[&str]] = &[
+    &["mail_server", "smtp_username"],
+    &["mail_server", "smtp_password"],
+    &["admin_token"],
+];
+const MASK: &str = "********";
+
+/// The default is only a placeholder until the config is loaded, it doesn't pass validation
 #[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     pub port: u16,
     pub mail_server: MailServerConfig,
     pub mail_message: MailMessageConfig,
+    /// Bearer token required for the admin api. Admin api is disabled if unset.
+    pub admin_token: Option<String>,
+    /// Registrations only become binding after confirming the mail address if set
+    pub double_opt_in: Option<DoubleOptInConfig>,
+    /// Registrators can request a link to fill in their archers from past registrations if set
+    pub prefill: Option<PrefillConfig>,
+    #[serde(default)]
+    pub spam_protection: SpamProtectionConfig,
+    /// CSV file (columns `code` and `name`) of the association's clubs, imported at startup
+    pub clubs_file: Option<PathBuf>,
+    /// TOML file with the class definitions. The bundled DSB rules are used if unset.
+    pub rules_file: Option<PathBuf>,
+    /// Bank account the entry fees are transferred to, given in the registration mails
+    #[serde(default)]
+    pub payment: PaymentConfig,
+    #[serde(default)]
+    pub archive: ArchiveConfig,
+    /// The SQLite database is backed up regularly while the server runs if set
+    pub backup: Option<BackupConfig>,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+impl Config {
+    /// Reads the config file, applies the environment overrides and secret files and validates
+    /// the result
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.to_owned(), e))?;
+        Self::from_toml(&content, std::env::vars())
+    }
+
+    fn from_toml(
+        content: &str,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Config, ConfigError> {
+        let mut value: toml::Value = toml::from_str(content).map_err(ConfigError::Parse)?;
+        let types = key_types();
+        let mut errors = Vec::new();
+        for (name, raw) in env {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let path: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+            if path
+                .iter()
+                .try_fold(&types, |types, key| types.get(key.as_str()))
+                .is_none()
+            {
+                errors.push(format!(
+                    "Environment variable {} doesn't name a config key",
+                    name
+                ));
+                continue;
+            }
+            if let Err(e) = override_key(&mut value, &types, &path, raw) {
+                errors.push(e);
+            }
+        }
+        // Misspelled keys would be ignored silently otherwise
+        errors.extend(
+            unknown_keys(&value, &types)
+                .into_iter()
+                .map(|key| format!("{} is no config key", key)),
+        );
+        for secret in SECRETS {
+            read_secret_file(&mut value, secret)?;
+        }
+        let config: Config = value.try_into().map_err(ConfigError::Parse)?;
+        if let Err(invalid) = config.validate() {
+            errors.extend(invalid);
+        }
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+        Ok(config)
+    }
+
+    /// Every problem of the config, so all of them can be fixed at once
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut check = |valid: bool, error: &str| {
+            if !valid {
+                errors.push(error.to_string());
+            }
+        };
+        check(self.port != 0, "port must be set");
+        if self.mail_server.transport == MailTransport::Smtp {
+            check(
+                !self.mail_server.smtp_server.is_empty(),
+                "mail_server.smtp_server must be set",
+            );
+            check(
+                self.mail_server.smtp_timeout_seconds > 0,
+                "mail_server.smtp_timeout_seconds must be positive",
+            );
+        }
+        check(
+            !self.mail_message.sender_name.is_empty(),
+            "mail_message.sender_name must be set",
+        );
+        check(
+            !self.mail_message.subject.is_empty(),
+            "mail_message.subject must be set",
+        );
+        for (key, mailbox) in self
+            .mail_message
+            .reply_to
+            .iter()
+            .map(|mailbox| ("mail_message.reply_to", mailbox))
+            .chain(
+                self.mail_message
+                    .bcc
+                    .iter()
+                    .map(|m| ("mail_message.bcc", m)),
+            )
+        {
+            check(
+                mailbox.parse::<lettre::message::Mailbox>().is_ok(),
+                &format!("{} {:?} is no valid mailbox", key, mailbox),
+            );
+        }
+        check(
+            self.admin_token
+                .as_ref()
+                .is_none_or(|token| !token.is_empty()),
+            "admin_token must not be empty, leave it out to disable the admin api",
+        );
+        if let Some(double_opt_in) = &self.double_opt_in {
+            check(
+                is_url(&double_opt_in.base_url),
+                "double_opt_in.base_url must be a http(s) url",
+            );
+            check(
+                !double_opt_in.subject.is_empty(),
+                "double_opt_in.subject must be set",
+            );
+            check(
+                double_opt_in.pending_timeout_hours > 0,
+                "double_opt_in.pending_timeout_hours must be positive",
+            );
+        }
+        if let Some(prefill) = &self.prefill {
+            check(
+                is_url(&prefill.base_url),
+                "prefill.base_url must be a http(s) url",
+            );
+            check(!prefill.subject.is_empty(), "prefill.subject must be set");
+            check(
+                prefill.link_timeout_hours > 0,
+                "prefill.link_timeout_hours must be positive",
+            );
+        }
+        check(
+            self.spam_protection.rate_limit_window_minutes > 0,
+            "spam_protection.rate_limit_window_minutes must be positive",
+        );
+        check(
+            self.spam_protection.max_body_bytes > 0,
+            "spam_protection.max_body_bytes must be positive",
+        );
+        for (key, file) in [
+            ("clubs_file", &self.clubs_file),
+            ("rules_file", &self.rules_file),
+        ] {
+            if let Some(file) = file {
+                check(file.is_file(), &format!("{} {:?} doesn't exist", key, file));
+            }
+        }
+        if let Some(backup) = &self.backup {
+            check(
+                backup.interval_hours > 0,
+                "backup.interval_hours must be positive",
+            );
+            check(backup.keep > 0, "backup.keep must be positive");
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The config as TOML, with the secrets masked
+    pub fn masked(&self) -> String {
+        let mut value = toml::Value::try_from(self).expect("Config is valid TOML");
+        for secret in SECRETS {
+            let (key, parents) = secret.split_last().unwrap();
+            if let Some(table) = table_at(&mut value, parents) {
+                if let Some(secret) = table.get_mut(*key) {
+                    *secret = toml::Value::String(MASK.to_string());
+                }
+            }
+        }
+        toml::to_string(&value).expect("Config is valid TOML")
+    }
+}
+
+/// Content of a secret file. A trailing line break is not part of the secret.
+pub fn read_secret(path: &Path) -> std::io::Result<String> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn is_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+fn table_at<'a>(value: &'a mut toml::Value, path: &[&str]) -> Option<&'a mut toml::value::Table> {
+    path.iter()
+        .try_fold(value, |value, key| value.get_mut(*key))?
+        .as_table_mut()
+}
+
+/// Every key of the config with a value of its type. Optional parts are set,
+/// so their keys are known as well, also the `_file` keys of the secrets.
+fn key_types() -> toml::Value {
+    let mut types = toml::Value::try_from(Config {
+        mail_server: MailServerConfig {
+            smtp_port: Some(0),
+            ..Default::default()
+        },
+        mail_message: MailMessageConfig {
+            reply_to: Some(String::new()),
+            ..Default::default()
+        },
+        admin_token: Some(String::new()),
+        double_opt_in: Some(DoubleOptInConfig {
+            base_url: String::new(),
+            subject: String::new(),
+            pending_timeout_hours: 0,
+        }),
+        prefill: Some(PrefillConfig {
+            base_url: String::new(),
+            subject: String::new(),
+            link_timeout_hours: 0,
+        }),
+        clubs_file: Some(PathBuf::new()),
+        rules_file: Some(PathBuf::new()),
+        backup: Some(BackupConfig::default()),
+        ..Default::default()
+    })
+    .expect("Config is valid TOML");
+    for secret in SECRETS {
+        let (key, parents) = secret.split_last().unwrap();
+        if let Some(table) = table_at(&mut types, parents) {
+            table.insert(format!("{}_file", key), toml::Value::String(String::new()));
+        }
+    }
+    types
+}
+
+/// Paths of the keys in `value` which aren't in `types`, e.g. `mail_server.smtp_pasword`
+fn unknown_keys(value: &toml::Value, types: &toml::Value) -> Vec<String> {
+    let (Some(table), Some(types)) = (value.as_table(), types.as_table()) else {
+        return Vec::new();
+    };
+    table
+        .iter()
+        .flat_map(|(key, value)| match types.get(key) {
+            Some(types) => unknown_keys(value, types)
+                .into_iter()
+                .map(|path| format!("{}.{}", key, path))
+                .collect(),
+            None => vec![key.clone()],
+        })
+        .collect()
+}
+
+/// Sets a key from an environment variable. Keys of a string in the config stay a string,
+/// otherwise the value is read as TOML, e.g. a number or boolean, and as string if that fails.
+fn override_key(
+    value: &mut toml::Value,
+    types: &toml::Value,
+    path: &[String],
+    raw: String,
+) -> Result<(), String> {
+    let (key, parents) = path.split_last().unwrap();
+    let mut table = value.as_table_mut().unwrap();
+    for parent in parents {
+        table = table
+            .entry(parent.clone())
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| format!("{} is no table", path.join(".")))?;
+    }
+    let parsed = match path
+        .iter()
+        .try_fold(types, |types, key| types.get(key.as_str()))
+    {
+        Some(toml::Value::String(_)) => None,
+        _ => toml::from_str::<toml::value::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value")),
+    };
+    table.insert(key.clone(), parsed.unwrap_or(toml::Value::String(raw)));
+    Ok(())
+}
+
+/// Replaces `<key>_file` with the content of the file
+fn read_secret_file(value: &mut toml::Value, secret: &[&str]) -> Result<(), ConfigError> {
+    let (key, parents) = secret.split_last().unwrap();
+    let file_key = format!("{}_file", key);
+    let Some(table) = table_at(value, parents) else {
+        return Ok(());
+    };
+    let Some(file) = table.remove(&file_key) else {
+        return Ok(());
+    };
+    let name = [parents, &[file_key.as_str()]].concat().join(".");
+    let path = PathBuf::from(
+        file.as_str()
+            .ok_or_else(|| ConfigError::Invalid(vec![format!("{} must be a path", name)]))?,
+    );
+    let secret = read_secret(&path).map_err(|e| ConfigError::Secret(name, path, e))?;
+    table.insert(key.to_string(), toml::Value::String(secret));
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(toml::de::Error),
+    Secret(String, PathBuf, std::io::Error),
+    Invalid(Vec<String>),
+    Rules(PathBuf, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "Couldn't read config file {:?}: {}", path, e),
+            ConfigError::Parse(e) => write!(f, "Couldn't parse config: {}", e),
+            ConfigError::Secret(key, path, e) => {
+                write!(f, "Couldn't read {} {:?}: {}", key, path, e)
+            }
+            ConfigError::Invalid(errors) => {
+                write!(f, "Invalid config:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+            ConfigError::Rules(path, e) => write!(f, "Couldn't load rules file {:?}: {}", path, e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct MailServerConfig {
+    pub transport: MailTransport,
     pub smtp_server: String,
+    /// Defaults to 465 with `tls`, 587 with `starttls` and 25 without tls
+    pub smtp_port: Option<u16>,
+    pub smtp_tls: TlsMode,
+    pub smtp_timeout_seconds: u64,
+    /// Mails are sent without authentication if empty
     pub smtp_username: String,
     pub smtp_password: String,
+    /// Directory the `file` transport writes the mails into, one `.eml` file per mail
+    pub mail_directory: PathBuf,
+}
+
+impl Default for MailServerConfig {
+    fn default() -> Self {
+        Self {
+            transport: MailTransport::default(),
+            smtp_server: String::new(),
+            smtp_port: None,
+            smtp_tls: TlsMode::default(),
+            smtp_timeout_seconds: 60,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+            mail_directory: PathBuf::from("mails"),
+        }
+    }
+}
+
+/// The `file` and `stdout` transports keep the mails local, for development and tests
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailTransport {
+    #[default]
+    Smtp,
+    File,
+    Stdout,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TlsMode {
+    None,
+    /// Upgrades a plain connection
+    Starttls,
+    /// Connects with tls right away
+    #[default]
+    Tls,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DoubleOptInConfig {
+    /// Public url of the registration page, used to build the confirmation link
+    pub base_url: String,
+    pub subject: String,
+    /// Registrations not confirmed within this time are purged
+    #[serde(default = "default_pending_timeout_hours")]
+    pub pending_timeout_hours: u32,
+}
+
+fn default_pending_timeout_hours() -> u32 {
+    48
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PrefillConfig {
+    /// Public url of the registration page, used to build the prefill link
+    pub base_url: String,
+    pub subject: String,
+    /// Prefill links can only be used once within this time
+    #[serde(default = "default_prefill_timeout_hours")]
+    pub link_timeout_hours: u32,
+}
+
+fn default_prefill_timeout_hours() -> u32 {
+    24
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpamProtectionConfig {
+    pub max_registrations_per_ip: usize,
+    pub max_registrations_per_mail: usize,
+    pub rate_limit_window_minutes: u64,
+    /// Registrations submitted faster after opening the form are rejected
+    pub min_fill_seconds: u32,
+    pub max_body_bytes: usize,
+    /// Take the client ip from the `X-Real-IP` header set by a reverse proxy
+    pub trust_proxy_headers: bool,
+}
+
+impl Default for SpamProtectionConfig {
+    fn default() -> Self {
+        Self {
+            max_registrations_per_ip: 10,
+            max_registrations_per_mail: 5,
+            rate_limit_window_minutes: 60,
+            min_fill_seconds: 5,
+            max_body_bytes: 64 * 1024,
+            trust_proxy_headers: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// Lists of archived tournaments are exported into a subdirectory per tournament
+    pub directory: PathBuf,
+    /// Personal data of archived tournaments is anonymized after this time
+    pub retention_days: u32,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("archive"),
+            retention_days: 2 * 365,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Backups are named by their time, e.g. `backup-2025-03-09_03-00-00.sqlite`
+    pub directory: PathBuf,
+    pub interval_hours: u32,
+    /// Number of backups kept, older ones are removed
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("backups"),
+            interval_hours: 24,
+            keep: 14,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,6 +521,20 @@ pub struct MailMessageConfig {
     pub sender_name: String,
     pub sender_address: EmailAddress,
     pub subject: String,
+    /// Mailbox answers go to, e.g. `Name <name@example.com>`. Answers go to the sender if unset.
+    #[serde(default)]
+    pub reply_to: Option<String>,
+    /// Mailboxes of the organizers, receiving a copy of every registration mail
+    #[serde(default)]
+    pub bcc: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct PaymentConfig {
+    /// The bank details are left out of the mails if empty
+    pub iban: String,
+    pub bic: String,
 }
 
 impl Default for MailMessageConfig {
@@ -29,6 +543,80 @@ impl Default for MailMessageConfig {
             sender_name: String::new(),
             sender_address: EmailAddress::from_str("example@mail.com").unwrap(),
             subject: String::new(),
+            reply_to: None,
+            bcc: Vec::new(),
         }
     }
 }
+
+#[test]
+fn test_env_overrides_and_secrets() {
+    let directory = std::env::temp_dir().join(format!("config-test-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let token_file = directory.join("admin_token");
+    std::fs::write(&token_file, "t0ken\n").unwrap();
+    let content = r#"
+        port = 3000
+        [mail_server]
+        smtp_server = "smtp.example.com"
+        smtp_username = "user"
+        smtp_password = "secret"
+        [mail_message]
+        sender_name = "PSV"
+        sender_address = "psv@example.com"
+        subject = "Registration"
+    "#;
+    let env = [
+        ("PSV_PORT", "8080"),
+        ("PSV_MAIL_SERVER__SMTP_PASSWORD", "1234"),
+        ("PSV_ADMIN_TOKEN_FILE", token_file.to_str().unwrap()),
+        ("PSV_BACKUP__KEEP", "3"),
+        ("HOME", "/root"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    let config = Config::from_toml(content, env).unwrap();
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.mail_server.smtp_password, "1234");
+    assert_eq!(config.admin_token.as_deref(), Some("t0ken"));
+    assert_eq!(config.backup.as_ref().unwrap().keep, 3);
+    let masked = config.masked();
+    assert!(!masked.contains("1234") && !masked.contains("t0ken"));
+    assert!(masked.contains("smtp_server = \"smtp.example.com\""));
+
+    // Strings stay strings, also if they are missing in the file
+    let env = [
+        ("PSV_MAIL_SERVER__SMTP_PASSWORD", "123456"),
+        ("PSV_ADMIN_TOKEN", "123456"),
+        ("PSV_MAIL_SERVER__SMTP_PORT", "2525"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    let without_password = content.replace("smtp_password = \"secret\"", "");
+    let config = Config::from_toml(&without_password, env).unwrap();
+    assert_eq!(config.mail_server.smtp_password, "123456");
+    assert_eq!(config.admin_token.as_deref(), Some("123456"));
+    assert_eq!(config.mail_server.smtp_port, Some(2525));
+
+    let Err(error) = Config::from_toml(content, [("PSV_PORT".to_string(), "0".to_string())]) else {
+        panic!("Port 0 must be refused");
+    };
+    assert_eq!(error.to_string(), "Invalid config:\n  port must be set");
+
+    // Misspelled keys are reported with the other errors
+    let misspelled = content.replace("smtp_password", "smtp_pasword");
+    let env = [
+        ("PSV_MAIL_SERVER__SMTP_SERVR", "smtp.example.com"),
+        ("PSV_PORT", "0"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+    let Err(error) = Config::from_toml(&misspelled, env) else {
+        panic!("Misspelled keys must be refused");
+    };
+    assert_eq!(
+        error.to_string(),
+        "Invalid config:\n  \
+         Environment variable PSV_MAIL_SERVER__SMTP_SERVR doesn't name a config key\n  \
+         mail_server.smtp_pasword is no config key\n  \
+         port must be set"
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,393 @@
+use crate::app::AppState;
+use crate::config::Config;
+use crate::tournament::{CurrentTournament, MailTournament};
+use crate::{
+    db, db::DbConnection, error::*, models::PendingRegistration, schema::pending_registrations,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Html,
+};
+use common::line_data::CreateArchersPayload;
+use common::locale::Locale;
+use common::tournament::Tournament;
+use diesel::prelude::*;
+use lettre::message::{header::ContentType, Mailbox};
+use rand::{distributions::Alphanumeric, Rng};
+use std::time::Duration;
+use tokio::sync::watch;
+
+const TOKEN_LENGTH: usize = 32;
+const PURGE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Stores the registration as pending and mails a confirmation link to the registrator
+pub async fn request_confirmation(
+    state: &AppState,
+    payload: &CreateArchersPayload,
+    current: &CurrentTournament,
+) -> Result<()> {
+    let pending = PendingRegistration {
+        token: rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect(),
+        payload: serde_json::to_string(payload).expect("Payload is always serializable"),
+        created_at: db::now(),
+        tournament_id: current.id,
+    };
+    let token = pending.token.clone();
+    db::run(&state.pool, move |conn| -> Result<()> {
+        diesel::insert_into(pending_registrations::table)
+            .values(pending)
+            .execute(conn)?;
+        Ok(())
+    })
+    .await?;
+
+    send_confirmation_mail(state, payload, &current.tournament, &token).await
+}
+
+/// Page behind the link in the confirmation mail. Only its button confirms the registration,
+/// so mail scanners and prefetchers following the link don't.
+pub async fn show_confirmation(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<(StatusCode, Html<String>)> {
+    let timeout_hours = pending_timeout_hours(&state.config);
+    let pending = db::run(&state.pool, move |conn| {
+        find_pending(conn, &token, timeout_hours)
+    })
+    .await?;
+    let Some((payload, _)) = pending else {
+        let (status, Html(text)) = invalid_link();
+        return Ok((status, Html(text.to_string())));
+    };
+    let (text, button) = match payload.locale {
+        Locale::En => ("Please confirm your registration.", "Confirm registration"),
+        Locale::De => ("Bitte bestätige deine Anmeldung.", "Anmeldung bestätigen"),
+    };
+    Ok((
+        StatusCode::OK,
+        Html(format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head><body>\
+             <form method=\"post\"><p>{}</p><button type=\"submit\">{}</button></form>\
+             </body></html>",
+            text, button
+        )),
+    ))
+}
+
+/// Makes a pending registration binding. Posted by the page behind the confirmation link.
+/// The registration is checked again, the tournament may have closed or the archers may have
+/// been registered by someone else in the meantime.
+pub async fn confirm_registration(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<(StatusCode, Html<&'static str>)> {
+    let timeout_hours = pending_timeout_hours(&state.config);
+    let pending_token = token.clone();
+    let Some((payload, tournament_id)) = db::run(&state.pool, move |conn| {
+        find_pending(conn, &pending_token, timeout_hours)
+    })
+    .await?
+    else {
+        return Ok(invalid_link());
+    };
+
+    let tournament = db::run(&state.pool, move |conn| {
+        crate::tournament::by_id(conn, tournament_id)
+    })
+    .await?;
+    if !tournament.is_open(chrono::Local::now().naive_local()) {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Html(match payload.locale {
+                Locale::En => "The registration is closed.",
+                Locale::De => "Die Anmeldung ist geschlossen.",
+            }),
+        ));
+    }
+    // The rules may have changed since the registration was submitted
+    if crate::archer::check_registration(&payload, &state.rules, &tournament).is_err() {
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(match payload.locale {
+                Locale::En => {
+                    "The registration doesn't fit the tournament's rules anymore. \
+                     Please register again."
+                }
+                Locale::De => {
+                    "Die Anmeldung passt nicht mehr zu den Regeln des Turniers. \
+                     Bitte melde erneut an."
+                }
+            }),
+        ));
+    }
+    if !payload.ignore_duplicates {
+        let archers = payload.archers.clone();
+        let duplicates = db::run(&state.pool, move |conn| {
+            crate::archer::find_duplicates(conn, &archers, tournament_id)
+        })
+        .await?;
+        if !duplicates.is_empty() {
+            log::info!(
+                "Rejected confirmation with {} suspected duplicates",
+                duplicates.len()
+            );
+            return Ok((
+                StatusCode::CONFLICT,
+                Html(match payload.locale {
+                    Locale::En => {
+                        "Some of the archers are registered already. \
+                         Please check the participants and register again."
+                    }
+                    Locale::De => {
+                        "Einige der Schützen sind bereits angemeldet. \
+                         Bitte prüfe die Teilnehmerliste und melde erneut an."
+                    }
+                }),
+            ));
+        }
+    }
+
+    let registered = crate::archer::register_archers(
+        &state,
+        &payload,
+        &CurrentTournament {
+            id: tournament_id,
+            tournament,
+        },
+        Some(token),
+    )
+    .await?;
+    if !registered {
+        return Ok(invalid_link());
+    }
+    Ok((
+        StatusCode::OK,
+        Html(match payload.locale {
+            Locale::En => "Registration confirmed. A confirmation mail was sent.",
+            Locale::De => "Anmeldung bestätigt. Bestätigungsmail wurde abgeschickt.",
+        }),
+    ))
+}
+
+fn invalid_link() -> (StatusCode, Html<&'static str>) {
+    (
+        StatusCode::NOT_FOUND,
+        Html("Der Link ist ungültig oder abgelaufen. / The link is invalid or expired."),
+    )
+}
+
+/// Deletes pending registrations which weren't confirmed in time, while double opt-in is enabled
+pub async fn purge_expired(states: watch::Receiver<AppState>) {
+    loop {
+        let state = states.borrow().clone();
+        if state.config.double_opt_in.is_some() {
+            let timeout_hours = pending_timeout_hours(&state.config);
+            match db::run(&state.pool, move |conn| delete_expired(conn, timeout_hours)).await {
+                Ok(0) => (),
+                Ok(purged) => log::info!("Purged {} unconfirmed registrations", purged),
+                Err(e) => log::error!("Couldn't purge unconfirmed registrations: {}", e),
+            }
+        }
+        tokio::time::sleep(PURGE_INTERVAL).await;
+    }
+}
+
+pub fn pending_timeout_hours(config: &Config) -> u32 {
+    config
+        .double_opt_in
+        .as_ref()
+        .map_or(0, |c| c.pending_timeout_hours)
+}
+
+/// Payload and tournament id of a pending registration if it's not expired yet
+fn find_pending(
+    connection: &mut DbConnection,
+    token: &str,
+    timeout_hours: u32,
+) -> Result<Option<(CreateArchersPayload, i32)>> {
+    let pending = pending_registrations::table
+        .find(token)
+        .filter(pending_registrations::created_at.ge(expiry_cutoff(timeout_hours)))
+        .first::<PendingRegistration>(connection)
+        .optional()?;
+    Ok(pending.and_then(|p| {
+        serde_json::from_str::<CreateArchersPayload>(&p.payload)
+            .map_err(|e| log::error!("Stored registration {} is invalid: {}", p.token, e))
+            .ok()
+            .map(|payload| (payload, p.tournament_id))
+    }))
+}
+
+/// Removes a pending registration while its archers are registered, in the same transaction.
+/// Returns false if it was confirmed already or expired in the meantime.
+pub fn take_pending(
+    connection: &mut DbConnection,
+    token: &str,
+    timeout_hours: u32,
+) -> Result<bool> {
+    let deleted = diesel::delete(
+        pending_registrations::table
+            .find(token)
+            .filter(pending_registrations::created_at.ge(expiry_cutoff(timeout_hours))),
+    )
+    .execute(connection)?;
+    Ok(deleted == 1)
+}
+
+fn delete_expired(connection: &mut DbConnection, timeout_hours: u32) -> Result<usize> {
+    Ok(diesel::delete(
+        pending_registrations::table
+            .filter(pending_registrations::created_at.lt(expiry_cutoff(timeout_hours))),
+    )
+    .execute(connection)?)
+}
+
+fn expiry_cutoff(timeout_hours: u32) -> chrono::NaiveDateTime {
+    db::now() - chrono::Duration::hours(timeout_hours.into())
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ConfirmationMailData {
+    tournament: MailTournament,
+    name: String,
+    club: String,
+    archers: Vec<String>,
+    confirmation_link: String,
+    valid_hours: u32,
+}
+
+async fn send_confirmation_mail(
+    state: &AppState,
+    payload: &CreateArchersPayload,
+    tournament: &Tournament,
+    token: &str,
+) -> Result<()> {
+    let (base_url, subject, valid_hours) = {
+        let double_opt_in = state
+            .config
+            .double_opt_in
+            .as_ref()
+            .expect("Confirmation mails are only sent with double opt-in enabled");
+        (
+            double_opt_in.base_url.clone(),
+            double_opt_in.subject.clone(),
+            double_opt_in.pending_timeout_hours,
+        )
+    };
+    let mail_data = ConfirmationMailData {
+        tournament: MailTournament::new(tournament, payload.locale),
+        name: payload.name.clone(),
+        club: payload.club.clone(),
+        archers: payload
+            .archers
+            .iter()
+            .map(|a| format!("{} {}", a.first_name, a.last_name))
+            .collect(),
+        confirmation_link: format!(
+            "{}/api/registrations/confirm/{}",
+            base_url.trim_end_matches('/'),
+            token
+        ),
+        valid_hours,
+    };
+    let email = crate::mail::message_builder(&state.config.mail_message)?
+        .to(Mailbox::new(
+            Some(payload.name.clone()),
+            payload.mail.as_str().parse()?,
+        ))
+        .header(ContentType::TEXT_PLAIN)
+        .subject(subject)
+        .body(state.templates.render(
+            match payload.locale {
+                Locale::En => "confirm_mail_en",
+                Locale::De => "confirm_mail",
+            },
+            &mail_data,
+        )?)
+        .unwrap();
+
+    state.mailer.send(email).await
+}
+
+#[test]
+fn test_take_pending_once() {
+    let Some(mut connection) = db::test_connection() else {
+        return;
+    };
+    let tournament = Tournament {
+        slug: "test".into(),
+        ..Default::default()
+    };
+    crate::tournament::save(&mut connection, &tournament).unwrap();
+    let (tournament_id, _) = crate::tournament::find(&mut connection, "test")
+        .unwrap()
+        .unwrap();
+    let pending = |token: &str, created_at: chrono::NaiveDateTime| PendingRegistration {
+        token: token.into(),
+        payload: "{}".into(),
+        created_at,
+        tournament_id,
+    };
+    diesel::insert_into(pending_registrations::table)
+        .values(vec![
+            pending("fresh", db::now()),
+            pending("expired", expiry_cutoff(1)),
+        ])
+        .execute(&mut connection)
+        .unwrap();
+    assert!(take_pending(&mut connection, "fresh", 48).unwrap());
+    assert!(!take_pending(&mut connection, "fresh", 48).unwrap());
+    assert!(!take_pending(&mut connection, "expired", 0).unwrap());
+    assert_eq!(delete_expired(&mut connection, 0).unwrap(), 1);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn test_confirmation_checks_archers() {
+    let config = Config {
+        double_opt_in: Some(crate::config::DoubleOptInConfig {
+            base_url: "https://example.com".into(),
+            subject: "Anmeldung bestätigen".into(),
+            pending_timeout_hours: 48,
+        }),
+        ..crate::app::test_config()
+    };
+    let (state, path) = crate::app::test_state("confirm", config);
+    let mut connection = state.pool.get().unwrap();
+    let (tournament_id, _) = crate::tournament::find(&mut connection, "indoor25")
+        .unwrap()
+        .unwrap();
+    // Classes of any gender need the gender of the archer
+    let payload = crate::archer::test_registration(vec![crate::archer::test_archer(
+        "Max",
+        "BU15",
+        "2013-05-03",
+        None,
+    )]);
+    diesel::insert_into(pending_registrations::table)
+        .values(PendingRegistration {
+            token: "invalid".into(),
+            payload: serde_json::to_string(&payload).unwrap(),
+            created_at: db::now(),
+            tournament_id,
+        })
+        .execute(&mut connection)
+        .unwrap();
+    let (status, _) = confirm_registration(State(state.clone()), Path("invalid".into()))
+        .await
+        .unwrap();
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let archers: i64 = crate::schema::archers::table
+        .count()
+        .get_result(&mut connection)
+        .unwrap();
+    assert_eq!(archers, 0);
+    drop(connection);
+    drop(state);
+    crate::app::remove_test_database(&path);
+}
@@ -1,8 +1,226 @@
-use diesel::prelude::*;
-use diesel::Connection;
+use crate::error::Result;
+use diesel::r2d2::{self, ConnectionManager};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations};
 
-pub fn establish_connection() -> SqliteConnection {
-    let database_url = std::env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set via environment variable or cli argument");
-    SqliteConnection::establish(&database_url).expect("Couldn't connect to database!")
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("Only one of the features `sqlite` and `postgres` can be enabled");
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("One of the features `sqlite` and `postgres` has to be enabled");
+
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::SqliteConnection;
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::PgConnection;
+
+#[cfg(feature = "sqlite")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
+#[cfg(feature = "postgres")]
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
+
+const MAX_CONNECTIONS: u32 = 8;
+
+pub type Pool = r2d2::Pool<ConnectionManager<DbConnection>>;
+
+/// Opens a pool of connections to the database, created once at startup.
+/// The url is a file path for SQLite and a `postgres://` url for PostgreSQL.
+pub fn create_pool(database_url: &str) -> std::result::Result<Pool, r2d2::PoolError> {
+    let builder = r2d2::Pool::builder().max_size(MAX_CONNECTIONS);
+    #[cfg(feature = "sqlite")]
+    let builder = builder.connection_customizer(Box::new(sqlite::ConnectionOptions));
+    builder.build(ConnectionManager::new(database_url))
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use diesel::connection::SimpleConnection;
+    use diesel::r2d2::{self, CustomizeConnection};
+    use diesel::SqliteConnection;
+    use std::time::Duration;
+
+    /// How long a connection waits for a lock held by another connection before failing
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Lets readers continue while a registration is written and makes writers wait for each other.
+    /// Foreign keys are only enforced by SQLite if turned on for every connection.
+    #[derive(Debug)]
+    pub struct ConnectionOptions;
+
+    impl CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionOptions {
+        fn on_acquire(
+            &self,
+            connection: &mut SqliteConnection,
+        ) -> std::result::Result<(), r2d2::Error> {
+            connection
+                .batch_execute(&format!(
+                    "PRAGMA busy_timeout = {}; PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;",
+                    BUSY_TIMEOUT.as_millis()
+                ))
+                .map_err(r2d2::Error::QueryError)
+        }
+    }
+}
+
+/// Applies the pending migrations and returns their versions.
+/// SQLite migrations rebuilding a table break its foreign keys in between, so they are only
+/// checked afterwards and turned on for the connection again.
+pub fn migrate(
+    connection: &mut DbConnection,
+) -> std::result::Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    use diesel_migrations::MigrationHarness;
+    #[cfg(feature = "sqlite")]
+    diesel::connection::SimpleConnection::batch_execute(connection, "PRAGMA foreign_keys = OFF;")?;
+    let migrated = connection
+        .run_pending_migrations(MIGRATIONS)?
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    #[cfg(feature = "sqlite")]
+    {
+        use diesel::RunQueryDsl;
+        #[derive(diesel::QueryableByName)]
+        struct Violation {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            table: String,
+        }
+        let violations: Vec<Violation> =
+            diesel::sql_query("PRAGMA foreign_key_check").load(connection)?;
+        if let Some(violation) = violations.first() {
+            return Err(format!(
+                "{} rows of {} reference missing rows",
+                violations.len(),
+                violation.table
+            )
+            .into());
+        }
+        diesel::connection::SimpleConnection::batch_execute(
+            connection,
+            "PRAGMA foreign_keys = ON;",
+        )?;
+    }
+    Ok(migrated)
+}
+
+/// Runs blocking database work with a connection of the pool, off the async executor
+pub async fn run<T, F>(pool: &Pool, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut DbConnection) -> Result<T> + Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut connection = pool.get()?;
+        f(&mut connection)
+    })
+    .await
+    .unwrap()
+}
+
+diesel::define_sql_function! {
+    /// Lower case of a text. SQLite only lowers ASCII letters, which is enough for mail addresses.
+    fn lower(text: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
+/// Current local time, timestamps in the database are local
+pub fn now() -> chrono::NaiveDateTime {
+    chrono::Local::now().naive_local()
+}
+
+/// Code an enum is stored with: its serde name, which is the name of its variant
+/// and stays stable across releases
+pub fn enum_code<T: serde::Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(code)) => code,
+        _ => panic!("Only enums with unit variants can be stored as code"),
+    }
+}
+
+/// Reads an enum stored with [`enum_code`]
+pub fn parse_enum_code<T: serde::de::DeserializeOwned>(code: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(code.to_string())).ok()
+}
+
+/// Migrated database for tests. SQLite runs in memory.
+/// PostgreSQL tests need `TEST_DATABASE_URL` and are skipped without it;
+/// their changes are rolled back at the end of the test.
+#[cfg(test)]
+pub fn test_connection() -> Option<DbConnection> {
+    use diesel::Connection;
+    #[cfg(feature = "sqlite")]
+    let mut connection = DbConnection::establish(":memory:").unwrap();
+    #[cfg(feature = "postgres")]
+    let mut connection = {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL not set, skipping PostgreSQL test");
+            return None;
+        };
+        let mut connection = DbConnection::establish(&url).unwrap();
+        connection.begin_test_transaction().unwrap();
+        connection
+    };
+    migrate(&mut connection).unwrap();
+    Some(connection)
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_pooled_connections_use_wal() {
+    use diesel::prelude::*;
+    #[derive(QueryableByName)]
+    struct JournalMode {
+        #[diesel(sql_type = diesel::sql_types::Text)]
+        journal_mode: String,
+    }
+    let path = std::env::temp_dir().join(format!("wal-test-{}.sqlite", std::process::id()));
+    let pool = create_pool(path.to_str().unwrap()).unwrap();
+    let mode = diesel::sql_query("PRAGMA journal_mode")
+        .get_result::<JournalMode>(&mut pool.get().unwrap())
+        .unwrap();
+    assert_eq!(mode.journal_mode, "wal");
+    drop(pool);
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_migrations_with_foreign_keys() {
+    use diesel::prelude::*;
+    #[derive(QueryableByName)]
+    struct ForeignKeys {
+        #[diesel(sql_type = diesel::sql_types::Integer)]
+        foreign_keys: i32,
+    }
+    let path = std::env::temp_dir().join(format!("fk-test-{}.sqlite", std::process::id()));
+    let pool = create_pool(path.to_str().unwrap()).unwrap();
+    let mut connection = pool.get().unwrap();
+    assert!(!migrate(&mut connection).unwrap().is_empty());
+    let enabled = diesel::sql_query("PRAGMA foreign_keys")
+        .get_result::<ForeignKeys>(&mut connection)
+        .unwrap();
+    assert_eq!(enabled.foreign_keys, 1);
+    // A registration of an unknown tournament
+    assert!(diesel::sql_query(
+        "INSERT INTO registrations (tournament_id, name, email, club, comment, locale, created_at) \
+         VALUES (42, '', '', '', '', 'De', '2025-01-01 00:00:00')"
+    )
+    .execute(&mut connection)
+    .is_err());
+    drop(connection);
+    drop(pool);
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+    }
+}
+
+#[test]
+fn test_enum_codes() {
+    use common::{bow_type::BowType, gender::Gender, target_face::TargetFace};
+    for bow_type in [BowType::Recurve, BowType::Compound, BowType::Traditional] {
+        assert_eq!(parse_enum_code(&enum_code(&bow_type)), Some(bow_type));
+    }
+    assert_eq!(enum_code(&BowType::Recurve), "Recurve");
+    assert_eq!(enum_code(&Gender::Female), "Female");
+    assert_eq!(enum_code(&TargetFace::M18Spot), "M18Spot");
+    assert_eq!(parse_enum_code::<Gender>("female"), None);
 }
@@ -2,13 +2,38 @@ use axum::{http::StatusCode, response::IntoResponse};
 use Error::*;
 
 /// All errors produced in the backend
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug)]
 pub enum Error {
-    MailError(lettre::transport::smtp::Error),
+    /// Mail couldn't be sent with the configured transport
+    MailError(Box<dyn std::error::Error + Send + Sync>),
     DBError(diesel::result::Error),
+    /// No connection of the pool became available
+    PoolError(diesel::r2d2::PoolError),
+    TemplateError(handlebars::RenderError),
+    /// Stored data couldn't be read
+    DataError(String),
+    /// The tournament is archived and must not change anymore
+    ArchivedError,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MailError(e) => e.fmt(f),
+            DBError(e) => e.fmt(f),
+            PoolError(e) => e.fmt(f),
+            TemplateError(e) => e.fmt(f),
+            DataError(e) => e.fmt(f),
+            ArchivedError => f.write_str("Tournament is archived"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
         match self {
@@ -28,13 +53,54 @@ impl IntoResponse for Error {
                 )
                     .into_response()
             }
+            Error::PoolError(e) => {
+                log::error!("{}", e);
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Datenbank ist nicht erreichbar".to_string(),
+                )
+                    .into_response()
+            }
+            Error::TemplateError(e) => {
+                log::info!("{}", e);
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Vorlage konnte nicht gerendert werden: {}", e),
+                )
+                    .into_response()
+            }
+            Error::DataError(e) => {
+                log::error!("{}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Gespeicherte Daten sind ungültig".to_string(),
+                )
+                    .into_response()
+            }
+            Error::ArchivedError => (
+                StatusCode::CONFLICT,
+                "Das Turnier ist archiviert und kann nicht mehr geändert werden".to_string(),
+            )
+                .into_response(),
         }
     }
 }
 
 impl From<lettre::transport::smtp::Error> for Error {
     fn from(e: lettre::transport::smtp::Error) -> Self {
-        MailError(e)
+        MailError(Box::new(e))
+    }
+}
+
+impl From<lettre::address::AddressError> for Error {
+    fn from(e: lettre::address::AddressError) -> Self {
+        MailError(Box::new(e))
+    }
+}
+
+impl From<lettre::transport::file::Error> for Error {
+    fn from(e: lettre::transport::file::Error) -> Self {
+        MailError(Box::new(e))
     }
 }
 
@@ -43,3 +109,15 @@ impl From<diesel::result::Error> for Error {
         DBError(e)
     }
 }
+
+impl From<diesel::r2d2::PoolError> for Error {
+    fn from(e: diesel::r2d2::PoolError) -> Self {
+        PoolError(e)
+    }
+}
+
+impl From<handlebars::RenderError> for Error {
+    fn from(e: handlebars::RenderError) -> Self {
+        TemplateError(e)
+    }
+}
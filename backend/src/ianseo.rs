@@ -0,0 +1,173 @@
+use crate::admin::AdminAuth;
+use crate::tournament::CurrentTournament;
+use crate::{db, db::DbConnection, error::*, models, schema};
+use axum::{
+    extract::State,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+};
+use common::bow_type::BowType;
+use common::gender::Gender;
+use diesel::prelude::*;
+use serde::Serialize;
+
+/// Row in the layout of the Ianseo participant table
+#[derive(Serialize)]
+pub struct IanseoArcher {
+    bib: i32,
+    /// Numbered from 1
+    session: i32,
+    division: &'static str,
+    class: String,
+    target: String,
+    #[serde(rename = "individual qualification")]
+    individual_qualification: i32,
+    #[serde(rename = "team qualification")]
+    team_qualification: i32,
+    #[serde(rename = "individual final")]
+    individual_final: i32,
+    #[serde(rename = "team final")]
+    team_final: i32,
+    #[serde(rename = "mixed team final")]
+    mixed_team_final: i32,
+    #[serde(rename = "last name")]
+    last_name: String,
+    #[serde(rename = "first name")]
+    first_name: String,
+    gender: Option<i32>,
+    #[serde(rename = "country code")]
+    country_code: String,
+    #[serde(rename = "country name")]
+    country_name: String,
+    #[serde(rename = "date of birth")]
+    date_of_birth: String,
+    subclass: String,
+    #[serde(rename = "country code 2")]
+    country_code_2: String,
+    #[serde(rename = "country name 2")]
+    country_name_2: String,
+    #[serde(rename = "country code 3")]
+    country_code_3: String,
+    #[serde(rename = "country name 3")]
+    country_name_3: String,
+}
+
+impl From<models::Archer> for IanseoArcher {
+    fn from(val: models::Archer) -> Self {
+        IanseoArcher {
+            bib: val.bib,
+            session: val.session + 1,
+            division: db::parse_enum_code::<BowType>(&val.bow_type)
+                .map_or("", |bow_type| bow_type.ianseo_division()),
+            class: val.class,
+            // Targets are assigned in Ianseo
+            target: String::new(),
+            individual_qualification: 1,
+            team_qualification: val.team_id.is_some() as i32,
+            individual_final: val.individual_final as i32,
+            team_final: val.team_final as i32,
+            mixed_team_final: val.mixed_team_final as i32,
+            last_name: val.last_name,
+            first_name: val.first_name,
+            gender: val
+                .gender
+                .and_then(|gender| db::parse_enum_code::<Gender>(&gender))
+                .map(|gender| gender.ianseo_code()),
+            country_code: val.club_code.unwrap_or_default(),
+            country_name: val.club_name,
+            date_of_birth: val.date_of_birth.format("%Y-%m-%d").to_string(),
+            subclass: String::new(),
+            country_code_2: String::new(),
+            country_name_2: String::new(),
+            country_code_3: String::new(),
+            country_name_3: String::new(),
+        }
+    }
+}
+
+/// Archers of the tournament in the layout of the Ianseo participant table
+pub fn ianseo_archers(
+    connection: &mut DbConnection,
+    tournament_id: i32,
+) -> Result<Vec<IanseoArcher>> {
+    use schema::{archers, registrations};
+    Ok(archers::table
+        .inner_join(registrations::table)
+        .filter(registrations::tournament_id.eq(tournament_id))
+        .order(archers::bib)
+        .select(models::Archer::as_select())
+        .load(connection)?
+        .into_iter()
+        .map(IanseoArcher::from)
+        .collect())
+}
+
+/// Csv of the archers, ready for importing into Ianseo
+pub fn to_csv(archers: Vec<IanseoArcher>) -> std::result::Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for archer in archers {
+        writer.serialize(archer)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+}
+
+/// Download of the Ianseo csv for the admins
+pub async fn export_archers(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+    current: CurrentTournament,
+) -> Result<impl IntoResponse> {
+    let archers = db::run(&pool, move |conn| ianseo_archers(conn, current.id)).await?;
+    let csv = to_csv(archers).map_err(|e| Error::DataError(e.to_string()))?;
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}-ianseo.csv\"",
+                    current.tournament.slug
+                ),
+            ),
+        ],
+        csv,
+    ))
+}
+
+#[test]
+fn test_ianseo_layout() {
+    let archer = models::Archer {
+        bib: 7,
+        registration_id: 1,
+        team_id: Some(2),
+        session: 0,
+        first_name: "Foo".into(),
+        last_name: "Bar".into(),
+        date_of_birth: chrono::NaiveDate::from_ymd_opt(1990, 3, 4).unwrap(),
+        gender: Some("Female".into()),
+        bow_type: "Compound".into(),
+        class: "CUE20W".into(),
+        target_face: "M18Spot".into(),
+        club_code: None,
+        club_name: "PSV".into(),
+        paid: true,
+        individual_final: true,
+        team_final: false,
+        mixed_team_final: true,
+    };
+    let csv = String::from_utf8(to_csv(vec![IanseoArcher::from(archer)]).unwrap()).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "bib,session,division,class,target,individual qualification,team qualification,\
+         individual final,team final,mixed team final,last name,first name,gender,\
+         country code,country name,date of birth,subclass,country code 2,country name 2,\
+         country code 3,country name 3"
+    );
+    assert_eq!(
+        lines.next().unwrap(),
+        "7,1,C,CUE20W,,1,1,1,0,1,Bar,Foo,1,,PSV,1990-03-04,,,,,"
+    );
+}
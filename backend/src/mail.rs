@@ -0,0 +1,150 @@
+use crate::config::{MailMessageConfig, MailServerConfig, MailTransport, TlsMode};
+use crate::error::*;
+use lettre::message::{Mailbox, MessageBuilder};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::transport::smtp::{PoolConfig, SMTP_PORT, SUBMISSIONS_PORT, SUBMISSION_PORT};
+#[cfg(test)]
+use lettre::transport::stub::AsyncStubTransport;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Message builder with sender and reply-to address already set
+pub fn message_builder(config: &MailMessageConfig) -> Result<MessageBuilder> {
+    let sender = config.sender_address.as_str().parse()?;
+    let builder = Message::builder().from(Mailbox::new(Some(config.sender_name.clone()), sender));
+    Ok(match &config.reply_to {
+        Some(reply_to) => builder.reply_to(reply_to.parse()?),
+        None => builder,
+    })
+}
+
+/// Connections to the SMTP server kept open for the following mails
+const SMTP_POOL_SIZE: u32 = 4;
+
+/// Transport the mails are sent with. Built once from the mail server config and shared
+/// by cloning, the clones use the same connection pool.
+#[derive(Clone)]
+pub enum Mailer {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>, String),
+    File(PathBuf),
+    Stdout,
+    /// Keeps the mails for inspection by tests
+    #[cfg(test)]
+    Stub(AsyncStubTransport),
+}
+
+impl Mailer {
+    pub fn new(config: &MailServerConfig) -> Result<Mailer> {
+        Ok(match config.transport {
+            MailTransport::Smtp => {
+                Mailer::Smtp(smtp_transport(config)?, config.smtp_server.clone())
+            }
+            MailTransport::File => Mailer::File(config.mail_directory.clone()),
+            MailTransport::Stdout => Mailer::Stdout,
+        })
+    }
+
+    pub async fn send(&self, email: Message) -> Result<()> {
+        match self {
+            Mailer::Smtp(transport, _) => {
+                transport.send(email).await?;
+            }
+            Mailer::File(directory) => {
+                create_directory(directory)?;
+                AsyncFileTransport::<Tokio1Executor>::new(directory)
+                    .send(email)
+                    .await?;
+            }
+            Mailer::Stdout => {
+                println!("{}", String::from_utf8_lossy(&email.formatted()));
+            }
+            #[cfg(test)]
+            Mailer::Stub(transport) => transport
+                .send(email)
+                .await
+                .map_err(|e| Error::MailError(Box::new(e)))?,
+        }
+        Ok(())
+    }
+
+    /// Connects to the SMTP server without sending a mail
+    pub async fn test_connection(&self) -> Result<bool> {
+        match self {
+            Mailer::Smtp(transport, _) => Ok(transport.test_connection().await?),
+            Mailer::File(directory) => create_directory(directory).map(|()| true),
+            Mailer::Stdout => Ok(true),
+            #[cfg(test)]
+            Mailer::Stub(_) => Ok(true),
+        }
+    }
+}
+
+impl std::fmt::Display for Mailer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mailer::Smtp(_, server) => write!(f, "SMTP server {}", server),
+            Mailer::File(directory) => write!(f, "mail directory {:?}", directory),
+            Mailer::Stdout => write!(f, "stdout"),
+            #[cfg(test)]
+            Mailer::Stub(_) => write!(f, "stub"),
+        }
+    }
+}
+
+fn smtp_transport(config: &MailServerConfig) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+    let tls_parameters = || TlsParameters::new(config.smtp_server.clone());
+    let (tls, default_port) = match config.smtp_tls {
+        TlsMode::None => (Tls::None, SMTP_PORT),
+        TlsMode::Starttls => (Tls::Required(tls_parameters()?), SUBMISSION_PORT),
+        TlsMode::Tls => (Tls::Wrapper(tls_parameters()?), SUBMISSIONS_PORT),
+    };
+    let builder = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_server)
+        .port(config.smtp_port.unwrap_or(default_port))
+        .tls(tls)
+        .timeout(Some(Duration::from_secs(config.smtp_timeout_seconds)))
+        .pool_config(PoolConfig::new().max_size(SMTP_POOL_SIZE));
+    Ok(if config.smtp_username.is_empty() {
+        builder.build()
+    } else {
+        builder
+            .credentials(Credentials::new(
+                config.smtp_username.clone(),
+                config.smtp_password.clone(),
+            ))
+            .build()
+    })
+}
+
+fn create_directory(directory: &Path) -> Result<()> {
+    std::fs::create_dir_all(directory).map_err(|e| Error::MailError(Box::new(e)))
+}
+
+#[tokio::test]
+async fn test_file_transport() {
+    let directory = std::env::temp_dir().join(format!("mail-test-{}", std::process::id()));
+    let mailer = Mailer::new(&MailServerConfig {
+        transport: MailTransport::File,
+        mail_directory: directory.clone(),
+        ..Default::default()
+    })
+    .unwrap();
+    assert!(mailer.test_connection().await.unwrap());
+    let email = Message::builder()
+        .from("psv@example.com".parse().unwrap())
+        .to("archer@example.com".parse().unwrap())
+        .subject("Anmeldung")
+        .body("Hallo".to_string())
+        .unwrap();
+    mailer.send(email).await.unwrap();
+    let mails: Vec<_> = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(mails.len(), 1);
+    assert_eq!(mails[0].extension().unwrap(), "eml");
+    let mail = std::fs::read_to_string(&mails[0]).unwrap();
+    assert!(mail.contains("Subject: Anmeldung") && mail.contains("To: archer@example.com"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,133 @@
+use crate::app::AppState;
+use crate::config::MailMessageConfig;
+use crate::mail::Mailer;
+use crate::{admin::RenderedMail, db, db::DbConnection, error::*, models, schema::mail_queue};
+use diesel::prelude::*;
+use lettre::message::header::ContentType;
+use std::time::Duration;
+use tokio::sync::watch;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 20;
+const MAX_ATTEMPTS: i32 = 5;
+
+/// Stores mails for delivery by [`process_queue`]. Returns the number of enqueued mails.
+pub fn enqueue(connection: &mut DbConnection, mails: Vec<RenderedMail>) -> Result<usize> {
+    let created_at = db::now();
+    connection.transaction(|conn| {
+        Ok(diesel::insert_into(mail_queue::table)
+            .values(
+                mails
+                    .into_iter()
+                    .map(|mail| models::InsertableQueuedMail {
+                        recipient: mail.to,
+                        subject: mail.subject,
+                        body: mail.body,
+                        created_at,
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .execute(conn)?)
+    })
+}
+
+/// Sends queued mails in the background. Failed mails are retried up to `MAX_ATTEMPTS` times.
+pub async fn process_queue(states: watch::Receiver<AppState>) {
+    loop {
+        let state = states.borrow().clone();
+        match db::run(&state.pool, pending_mails).await {
+            Ok(mails) => {
+                for mail in mails {
+                    let result =
+                        send_queued_mail(&state.mailer, &state.config.mail_message, &mail).await;
+                    if let Err(e) = &result {
+                        log::warn!(
+                            "Sending queued mail {} to {} failed: {}",
+                            mail.id,
+                            mail.recipient,
+                            e
+                        );
+                    }
+                    if let Err(e) =
+                        db::run(&state.pool, move |conn| mark_attempt(conn, mail.id, result)).await
+                    {
+                        log::error!("Couldn't update mail queue: {}", e);
+                    }
+                }
+            }
+            Err(e) => log::error!("Couldn't read mail queue: {}", e),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn pending_mails(connection: &mut DbConnection) -> Result<Vec<models::QueuedMail>> {
+    Ok(mail_queue::table
+        .filter(mail_queue::sent_at.is_null())
+        .filter(mail_queue::attempts.lt(MAX_ATTEMPTS))
+        .order(mail_queue::id)
+        .limit(BATCH_SIZE)
+        .select(models::QueuedMail::as_select())
+        .load(connection)?)
+}
+
+async fn send_queued_mail(
+    mailer: &Mailer,
+    sender: &MailMessageConfig,
+    mail: &models::QueuedMail,
+) -> std::result::Result<(), String> {
+    let email = crate::mail::message_builder(sender)
+        .map_err(|e| e.to_string())?
+        .to(mail.recipient.parse().map_err(|e| format!("{}", e))?)
+        .header(ContentType::TEXT_PLAIN)
+        .subject(&mail.subject)
+        .body(mail.body.clone())
+        .map_err(|e| e.to_string())?;
+    mailer.send(email).await.map_err(|e| e.to_string())
+}
+
+fn mark_attempt(
+    connection: &mut DbConnection,
+    id: i32,
+    result: std::result::Result<(), String>,
+) -> Result<()> {
+    let mail = mail_queue::table.find(id);
+    match result {
+        Ok(()) => diesel::update(mail)
+            .set((
+                mail_queue::sent_at.eq(db::now()),
+                mail_queue::attempts.eq(mail_queue::attempts + 1),
+            ))
+            .execute(connection)?,
+        Err(e) => diesel::update(mail)
+            .set((
+                mail_queue::last_error.eq(e),
+                mail_queue::attempts.eq(mail_queue::attempts + 1),
+            ))
+            .execute(connection)?,
+    };
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_send_queued_mail() {
+    let stub = lettre::transport::stub::AsyncStubTransport::new_ok();
+    let mailer = Mailer::Stub(stub.clone());
+    let mail = models::QueuedMail {
+        id: 1,
+        recipient: "archer@example.com".into(),
+        subject: "Turnier".into(),
+        body: "Hallo".into(),
+    };
+    let sender = MailMessageConfig::default();
+    send_queued_mail(&mailer, &sender, &mail).await.unwrap();
+    let invalid = models::QueuedMail {
+        recipient: "archer".into(),
+        ..mail
+    };
+    assert!(send_queued_mail(&mailer, &sender, &invalid).await.is_err());
+    let messages = stub.messages().await;
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].0.to()[0].to_string(), "archer@example.com");
+    assert!(messages[0].1.contains("Subject: Turnier"));
+}
@@ -1,44 +1,45 @@
-use axum::{
-    body::{boxed, Body, BoxBody},
-    http::{Request, Response, StatusCode, Uri},
-    response::AppendHeaders,
-    routing::{get, post},
-    Router,
+use app::AppState;
+use clap::{Parser, Subcommand};
+use common::class::Rules;
+use config::{Config, ConfigError};
+use diesel_migrations::MigrationHarness;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Mutex,
 };
-use clap::Parser;
-use config::Config;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use handlebars::Handlebars;
-use lazy_static::lazy_static;
-use static_init::dynamic;
-use std::{net::SocketAddr, path::PathBuf};
-use tower::ServiceExt;
-use tower_http::services::ServeDir;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
 
+mod admin;
+mod app;
 mod archer;
+mod archive;
+#[cfg(feature = "sqlite")]
+mod backup;
+mod club;
 mod config;
+mod confirmation;
 mod db;
 mod error;
+mod ianseo;
+mod mail;
+mod mail_queue;
 mod models;
+mod prefill;
 mod schema;
-
-#[dynamic()]
-pub static mut CONFIG: Config = Config::default();
-
-#[dynamic()]
-pub static mut HANDLEBARS: Handlebars<'static> = {
-    let mut hlbs = Handlebars::new();
-    hlbs.set_strict_mode(true);
-    hlbs
-};
+mod spam;
+mod tournament;
 
 #[derive(Parser, Debug)]
 struct CliArgs {
-    /// Path to config file
+    /// Path to config file.
+    /// Keys can be overridden by environment variables like `PSV_MAIL_SERVER__SMTP_SERVER`
     #[arg(long, default_value_t = String::from("config.toml"))]
     config_file: String,
 
-    /// Path to database file. Overwrites environment variable
+    /// Path to the SQLite database file or url of the PostgreSQL database.
+    /// Overwrites environment variable
     #[arg(long)]
     database_file: Option<String>,
 
@@ -46,99 +47,421 @@ struct CliArgs {
     /// Overwrites password from config
     #[arg(long)]
     mail_password_file: Option<PathBuf>,
+
+    /// Starts the server if missing
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Starts the server
+    Serve,
+    /// Migrates the database to the current schema
+    Migrate,
+    /// Lists the tournaments, or the archers of a tournament
+    List { slug: Option<String> },
+    /// Exports the archers of a tournament in the layout of the Ianseo participant table
+    Export {
+        slug: String,
+        /// Csv file to write. Written to stdout if missing.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Sends the registration mail of an archer's registration again
+    ResendMail { bib: i32 },
+    /// Deletes an archer
+    Delete { bib: i32 },
+    /// Checks the config, rules and mail templates and the mail transport
+    CheckConfig,
+    /// Prints the effective config, including environment overrides, with secrets masked
+    PrintConfig,
+    /// Sends a mail with the configured settings
+    SendTestMail { to: lettre::Address },
+    /// Closes a finished tournament for good and exports its lists to the archive directory
+    Archive { slug: String },
+    /// Creates the next edition of a tournament with all dates shifted
+    Rollover {
+        slug: String,
+        new_slug: String,
+        /// Number of years between the editions
+        #[arg(long, default_value_t = 1)]
+        years: i32,
+    },
+    /// Anonymizes the archers of archived tournaments after the retention period
+    Anonymize,
+    /// Backs up the SQLite database into the backup directory
+    #[cfg(feature = "sqlite")]
+    Backup,
+    /// Replaces the SQLite database with a backup. The server has to be stopped.
+    #[cfg(feature = "sqlite")]
+    Restore { backup: PathBuf },
+}
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
-    let args = CliArgs::parse();
-    if let Some(db_file) = args.database_file {
-        std::env::set_var("DATABASE_URL", db_file);
+    let mut args = CliArgs::parse();
+    let (config, rules) = load_config(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let command = args.command.take().unwrap_or(Command::Serve);
+    match &command {
+        Command::PrintConfig => {
+            print!("{}", config.masked());
+            return;
+        }
+        Command::CheckConfig => {
+            check_config(&config).await;
+            return;
+        }
+        Command::SendTestMail { to } => {
+            send_test_mail(&config, to.clone()).await;
+            return;
+        }
+        _ => (),
     }
-    db::establish_connection()
-        .run_pending_migrations(MIGRATIONS)
-        .expect("Could not migrate database");
 
-    *CONFIG.write() = {
-        let mut config = load_config(&std::path::PathBuf::from(&args.config_file));
-        if let Some(pswd) = args.mail_password_file {
-            config.mail_server.smtp_password =
-                std::fs::read_to_string(pswd).expect("Password file couldn't be read");
+    let database_url = args
+        .database_file
+        .clone()
+        .or_else(|| std::env::var("DATABASE_URL").ok())
+        .expect("DATABASE_URL must be set via environment variable or cli argument");
+    // Restored before any connection is opened, which would keep using the replaced file
+    #[cfg(feature = "sqlite")]
+    if let Command::Restore { backup } = &command {
+        let migrated = backup::restore(Path::new(&database_url), backup)
+            .unwrap_or_else(|e| panic!("Couldn't restore {:?}: {}", backup, e));
+        println!("Restored database from {:?}", backup);
+        for version in migrated {
+            println!("Applied migration {}", version);
+        }
+        return;
+    }
+    let pool = db::create_pool(&database_url).expect("Couldn't connect to database!");
+    let mut connection = pool.get().expect("Couldn't connect to database!");
+    // Other commands leave the schema alone, so a backup taken before an upgrade has the old one
+    let migrated = match command {
+        Command::Serve | Command::Migrate => {
+            db::migrate(&mut connection).expect("Could not migrate database")
+        }
+        #[cfg(feature = "sqlite")]
+        Command::Backup => Vec::new(),
+        _ if connection
+            .has_pending_migration(db::MIGRATIONS)
+            .expect("Couldn't read the applied migrations") =>
+        {
+            eprintln!("The database has to be migrated first, run the migrate command");
+            std::process::exit(1);
         }
-        config
+        _ => Vec::new(),
     };
-    {
-        let mut handlebars = HANDLEBARS.write();
-        // handlebars.set_strict_mode(true);
-        handlebars.set_dev_mode(cfg!(debug_assertions));
-        handlebars
-            .register_template_string("user_mail_en", include_str!("../user_mail_en.tpl"))
-            .unwrap();
-        handlebars
-            .register_template_string("user_mail", include_str!("../user_mail.tpl"))
-            .unwrap();
+    drop(connection);
+    let state = AppState::new(config, rules, pool)
+        .unwrap_or_else(|e| panic!("Couldn't set up the server: {}", e));
+    match command {
+        Command::Serve => serve(state, &database_url, args).await,
+        Command::Migrate if migrated.is_empty() => println!("Database is up to date"),
+        Command::Migrate => {
+            for version in migrated {
+                println!("Applied migration {}", version);
+            }
+        }
+        command => run_command(&state, &database_url, command).await,
+    }
+}
+
+/// Config file with the environment overrides and secret files applied, and the rules it uses
+fn load_config(args: &CliArgs) -> Result<(Config, Rules), ConfigError> {
+    let mut config = Config::load(Path::new(&args.config_file))?;
+    if let Some(pswd) = &args.mail_password_file {
+        config.mail_server.smtp_password = config::read_secret(pswd)
+            .map_err(|e| ConfigError::Secret("mail_password_file".to_string(), pswd.clone(), e))?;
     }
+    let rules = match &config.rules_file {
+        Some(rules_file) => load_rules(rules_file)?,
+        None => Rules::default(),
+    };
+    Ok((config, rules))
+}
 
-    let api = Router::new()
-        .route("/archers", post(archer::create_archers))
-        .route("/archers", get(archer::list_archers));
-    let app = Router::new()
-        .nest_service(
-            "/",
-            get(handler).then(|res| async move {
-                Ok((
-                    AppendHeaders([(axum::http::header::CACHE_CONTROL, "no-cache")]),
-                    res,
-                ))
-            }),
-        )
-        .nest_service("/api", api);
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], CONFIG.read().port));
+// The database url is only needed for the SQLite backups
+#[cfg_attr(feature = "postgres", allow(unused_variables))]
+async fn serve(state: AppState, database_url: &str, args: CliArgs) {
+    import_clubs(&state).unwrap_or_else(|e| panic!("Couldn't import clubs: {}", e));
+    app::check_mailer(&state.mailer).await;
+
+    let (states, _) = watch::channel(state.clone());
+    let (apps, current) = watch::channel(Mutex::new(app::build_app(state.clone())));
+    tokio::spawn(mail_queue::process_queue(states.subscribe()));
+    tokio::spawn(confirmation::purge_expired(states.subscribe()));
+    #[cfg(feature = "sqlite")]
+    tokio::spawn(backup::schedule(
+        PathBuf::from(database_url),
+        states.subscribe(),
+    ));
+    #[cfg(feature = "postgres")]
+    if let Some(backup_config) = &state.config.backup {
+        log::warn!(
+            "Backups to {:?} are only made of SQLite databases, use pg_dump for PostgreSQL",
+            backup_config.directory
+        );
+    }
+    tokio::spawn(reload_on_hangup(args, states, apps));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], state.config.port));
     println!("listening on http://{}", addr);
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app::current_app(current).into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
 
-async fn handler(uri: Uri) -> Result<Response<BoxBody>, (StatusCode, String)> {
-    let res = get_static_file(uri.clone()).await?;
-
-    if res.status() == StatusCode::NOT_FOUND {
-        // try with `.html`
-        // TODO: handle if the Uri has query parameters
-        match format!("{}.html", uri).parse() {
-            Ok(uri_html) => get_static_file(uri_html).await,
-            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Invalid URI".to_string())),
+/// Reloads the config and the rules on SIGHUP. Requests and background tasks, also the backups,
+/// use the new state once it's valid, an invalid config keeps the previous one.
+/// The clubs file is imported again, only a changed port needs a restart.
+async fn reload_on_hangup(
+    args: CliArgs,
+    states: watch::Sender<AppState>,
+    apps: watch::Sender<app::SharedApp>,
+) {
+    let mut hangups = signal(SignalKind::hangup()).expect("Couldn't listen for SIGHUP");
+    while hangups.recv().await.is_some() {
+        let current = states.borrow().clone();
+        let reloaded = load_config(&args)
+            .map_err(|e| e.to_string())
+            .and_then(|(config, rules)| current.reload(config, rules).map_err(|e| e.to_string()));
+        match reloaded {
+            Ok(state) => {
+                if state.config.port != current.config.port {
+                    log::warn!("Changes of port take effect after a restart");
+                }
+                app::check_mailer(&state.mailer).await;
+                let importing = state.clone();
+                if let Err(e) = tokio::task::spawn_blocking(move || import_clubs(&importing))
+                    .await
+                    .unwrap()
+                {
+                    log::error!("Couldn't import clubs: {}", e);
+                }
+                apps.send_replace(Mutex::new(app::build_app(state.clone())));
+                states.send_replace(state);
+                log::info!("Reloaded config");
+            }
+            Err(e) => log::error!("Kept the previous config, reloading failed: {}", e),
         }
-    } else {
-        Ok(res)
     }
 }
 
-async fn get_static_file(uri: Uri) -> Result<Response<BoxBody>, (StatusCode, String)> {
-    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+/// Inserts or updates the clubs of the clubs file, if the config has one
+fn import_clubs(state: &AppState) -> Result<(), String> {
+    let Some(clubs_file) = &state.config.clubs_file else {
+        return Ok(());
+    };
+    let mut connection = state.pool.get().map_err(|e| e.to_string())?;
+    let imported = club::import_clubs(&mut connection, clubs_file)
+        .map_err(|e| format!("{:?}: {}", clubs_file, e))?;
+    log::info!("Imported {} clubs from {:?}", imported, clubs_file);
+    Ok(())
+}
+
+fn load_rules(path: &Path) -> Result<Rules, ConfigError> {
+    let rules = |e: &dyn std::fmt::Display| ConfigError::Rules(path.to_owned(), e.to_string());
+    let toml_rules = std::fs::read_to_string(path).map_err(|e| rules(&e))?;
+    Rules::from_toml(&toml_rules).map_err(|e| rules(&e))
+}
 
-    lazy_static! {
-        static ref HTML_PATH: String =
-            std::env::var("WEBPAGE").unwrap_or_else(|_| "../frontend/dist".to_string());
+/// Config and rules are checked while loading, so only the templates and the mail transport are left
+async fn check_config(config: &Config) {
+    println!("Config and rules are valid");
+    let mut valid = true;
+    match app::load_templates() {
+        Ok(_) => println!("Mail templates are valid"),
+        Err(e) => {
+            println!("Invalid mail template: {}", e);
+            valid = false;
+        }
     }
-
-    // `ServeDir` implements `tower::Service` so we can call it with `tower::ServiceExt::oneshot`
-    match ServeDir::new(&*HTML_PATH).oneshot(req).await {
-        Ok(res) => Ok(res.map(boxed)),
-        Err(err) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Something went wrong: {}", err),
-        )),
+    match mail::Mailer::new(&config.mail_server) {
+        Ok(mailer) => match mailer.test_connection().await {
+            Ok(true) => println!("Mails are sent to {}", mailer),
+            Ok(false) => {
+                println!("{} didn't respond", mailer);
+                valid = false;
+            }
+            Err(e) => {
+                println!("Couldn't connect to {}: {}", mailer, e);
+                valid = false;
+            }
+        },
+        Err(e) => {
+            println!("Invalid mail server settings: {}", e);
+            valid = false;
+        }
     }
+    if !valid {
+        std::process::exit(1);
+    }
+}
+
+async fn send_test_mail(config: &Config, to: lettre::Address) {
+    let mail = mail::message_builder(&config.mail_message)
+        .expect("Invalid sender address")
+        .to(lettre::message::Mailbox::new(None, to.clone()))
+        .header(lettre::message::header::ContentType::TEXT_PLAIN)
+        .subject("Testmail")
+        .body(format!(
+            "Diese Mail wurde mit den Einstellungen von {} verschickt.",
+            config.mail_message.sender_address
+        ))
+        .unwrap();
+    mail::Mailer::new(&config.mail_server)
+        .expect("Invalid mail server settings")
+        .send(mail)
+        .await
+        .unwrap_or_else(|e| panic!("Couldn't send mail to {}: {}", to, e));
+    println!("Sent test mail to {}", to);
 }
 
-fn load_config(path: &std::path::Path) -> Config {
-    let toml_config = std::fs::read_to_string(path)
-        .unwrap_or_else(|_| panic!("Couldn't read file from path {:?}", path));
-    toml::from_str(&toml_config).expect("Couldn't parse config content!")
+// The database url is only needed for the SQLite backups
+#[cfg_attr(feature = "postgres", allow(unused_variables))]
+async fn run_command(state: &AppState, database_url: &str, command: Command) {
+    let mut connection = state.pool.get().expect("Couldn't connect to database!");
+    let find_tournament = |connection: &mut db::DbConnection, slug: &str| {
+        tournament::find(connection, slug)
+            .expect("Couldn't load tournament")
+            .unwrap_or_else(|| panic!("Unknown tournament {}", slug))
+    };
+    match command {
+        Command::Serve
+        | Command::Migrate
+        | Command::CheckConfig
+        | Command::PrintConfig
+        | Command::SendTestMail { .. } => unreachable!("handled before"),
+        #[cfg(feature = "sqlite")]
+        Command::Restore { .. } => unreachable!("handled before"),
+        Command::List { slug: None } => {
+            for (_, tournament) in
+                tournament::all(&mut connection).expect("Couldn't load tournaments")
+            {
+                println!(
+                    "{}\t{}\t{}{}",
+                    tournament.slug,
+                    tournament.date,
+                    tournament.name,
+                    if tournament.archived_at.is_some() {
+                        " (archived)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        Command::List { slug: Some(slug) } => {
+            let (id, _) = find_tournament(&mut connection, &slug);
+            println!("bib\tsession\tclass\tname\tclub\tpaid");
+            for archer in archer::archers_of(&mut connection, id).expect("Couldn't load archers") {
+                println!(
+                    "{}\t{}\t{}\t{} {}\t{}\t{}",
+                    archer.bib,
+                    archer.session + 1,
+                    archer.class,
+                    archer.first_name,
+                    archer.last_name,
+                    archer.club_name,
+                    if archer.paid { "yes" } else { "no" }
+                );
+            }
+        }
+        Command::Export { slug, output } => {
+            let (id, _) = find_tournament(&mut connection, &slug);
+            let archers =
+                ianseo::ianseo_archers(&mut connection, id).expect("Couldn't load archers");
+            let count = archers.len();
+            let csv = ianseo::to_csv(archers).expect("Couldn't write csv");
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, csv)
+                        .unwrap_or_else(|e| panic!("Couldn't write {:?}: {}", path, e));
+                    println!("Exported {} archers to {:?}", count, path);
+                }
+                None => {
+                    use std::io::Write;
+                    std::io::stdout()
+                        .write_all(&csv)
+                        .expect("Couldn't write csv");
+                }
+            }
+        }
+        Command::ResendMail { bib } => {
+            if archer::resend_registration_mail(state, bib)
+                .await
+                .expect("Couldn't send registration mail")
+            {
+                println!("Sent registration mail of archer {} again", bib);
+            } else {
+                panic!("Unknown archer {}", bib);
+            }
+        }
+        Command::Delete { bib } => {
+            let deleted = archer::delete_archer(&mut connection, bib)
+                .expect("Couldn't delete archer")
+                .unwrap_or_else(|| panic!("Unknown archer {}", bib));
+            println!(
+                "Deleted {} {} ({})",
+                deleted.archer.first_name, deleted.archer.last_name, bib
+            );
+            match deleted.team_members_left {
+                Some(0) => println!("Deleted the team of the archer, it had no members left"),
+                Some(members) => println!(
+                    "The team of the archer has {} members left, check its class",
+                    members
+                ),
+                None => (),
+            }
+        }
+        Command::Archive { slug } => {
+            let (id, tournament) = find_tournament(&mut connection, &slug);
+            let directory =
+                archive::archive(&mut connection, id, &tournament, &state.config.archive)
+                    .unwrap_or_else(|e| panic!("Couldn't archive tournament: {}", e));
+            println!("Archived {} to {:?}", tournament.name, directory);
+        }
+        Command::Rollover {
+            slug,
+            new_slug,
+            years,
+        } => {
+            let (_, tournament) = find_tournament(&mut connection, &slug);
+            if tournament::find(&mut connection, &new_slug)
+                .expect("Couldn't load tournament")
+                .is_some()
+            {
+                panic!("Tournament {} already exists", new_slug);
+            }
+            let next = archive::rollover(&mut connection, &tournament, new_slug, years)
+                .expect("Couldn't create next edition");
+            println!("Created {} ({}) on {}", next.name, next.slug, next.date);
+        }
+        Command::Anonymize => {
+            let anonymized = archive::anonymize(&mut connection, &state.config.archive)
+                .unwrap_or_else(|e| panic!("Couldn't anonymize archers: {}", e));
+            println!("Anonymized {} archers", anonymized.archers);
+            for slug in anonymized.tournaments {
+                println!("Rewrote the exported lists of {}", slug);
+            }
+            println!(
+                "Removed {} queued mails and {} prefill links of anonymized addresses",
+                anonymized.queued_mails, anonymized.prefill_tokens
+            );
+        }
+        #[cfg(feature = "sqlite")]
+        Command::Backup => {
+            let config = state.config.backup.clone().unwrap_or_default();
+            let path = backup::backup(std::path::Path::new(database_url), &config)
+                .expect("Couldn't back up database");
+            println!("Backed up database to {:?}", path);
+        }
+    }
 }
@@ -1,61 +1,172 @@
-use crate::schema::{archer_additions, archers};
+use crate::schema::{
+    archers, clubs, mail_queue, pending_registrations, prefill_tokens, registrations, teams,
+    tournaments,
+};
 use diesel::prelude::*;
 
-#[derive(Queryable)]
+/// Registered archer. Gender and bow type are stored with the names of their variants.
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = archers)]
 pub struct Archer {
     pub bib: i32,
+    pub registration_id: i32,
+    pub team_id: Option<i32>,
+    /// Index into the sessions of the tournament, followed by the waiting lists
     pub session: i32,
-    pub division: String,
-    pub class: String,
-    pub target: String,
-    pub individual_qualification: i32,
-    pub team_qualification: i32,
-    pub individual_final: i32,
-    pub team_final: i32,
-    pub mixed_team_final: i32,
-    pub last_name: String,
     pub first_name: String,
-    pub gender: Option<i32>,
-    pub country_code: String,
-    pub country_name: String,
-    pub date_of_birth: String,
-    pub subclass: Option<String>,
-    pub country_code_2: Option<String>,
-    pub country_name_2: Option<String>,
-    pub country_code_3: Option<String>,
-    pub country_name_3: Option<String>,
-}
-
-#[derive(Insertable, Default)]
+    pub last_name: String,
+    pub date_of_birth: chrono::NaiveDate,
+    pub gender: Option<String>,
+    pub bow_type: String,
+    pub class: String,
+    pub target_face: String,
+    /// Code in the club registry. Missing for unknown clubs.
+    pub club_code: Option<String>,
+    pub club_name: String,
+    pub paid: bool,
+    pub individual_final: bool,
+    pub team_final: bool,
+    pub mixed_team_final: bool,
+}
+
+#[derive(Insertable)]
 #[diesel(table_name = archers)]
 pub struct InsertableArcher {
+    pub registration_id: i32,
     pub session: i32,
-    pub division: String,
-    pub class: String,
-    pub target: String,
-    pub individual_qualification: i32,
-    pub team_qualification: i32,
-    pub individual_final: i32,
-    pub team_final: i32,
-    pub mixed_team_final: i32,
-    pub last_name: String,
     pub first_name: String,
-    pub gender: Option<i32>,
-    pub country_code: String,
-    pub country_name: String,
-    pub date_of_birth: String,
-    pub subclass: Option<String>,
-    pub country_code_2: String,
-    pub country_name_2: String,
-    pub country_code_3: String,
-    pub country_name_3: String,
+    pub last_name: String,
+    pub date_of_birth: chrono::NaiveDate,
+    pub gender: Option<String>,
+    pub bow_type: String,
+    pub class: String,
+    pub target_face: String,
+    pub club_code: Option<String>,
+    pub club_name: String,
+    pub individual_final: bool,
+    pub team_final: bool,
+    pub mixed_team_final: bool,
 }
 
-#[derive(Insertable, Queryable)]
-#[diesel(table_name = archer_additions)]
-pub struct ArcherAdditions {
-    pub bib: i32,
+/// Submitted form, the archers and teams reference it
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = registrations)]
+pub struct Registration {
+    pub tournament_id: i32,
+    pub name: String,
+    /// Missing once anonymized
+    pub email: Option<String>,
+    pub club: String,
+    pub comment: String,
+    pub locale: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = registrations)]
+pub struct InsertableRegistration {
+    pub tournament_id: i32,
+    pub name: String,
     pub email: String,
+    pub club: String,
     pub comment: String,
-    pub target_face: String,
+    pub locale: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = mail_queue)]
+pub struct QueuedMail {
+    pub id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = mail_queue)]
+pub struct InsertableQueuedMail {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Queryable)]
+#[diesel(table_name = pending_registrations)]
+pub struct PendingRegistration {
+    pub token: String,
+    pub payload: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub tournament_id: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = teams)]
+pub struct InsertableTeam {
+    pub registration_id: i32,
+    pub name: String,
+    pub class: String,
+}
+
+#[derive(Insertable, Queryable)]
+#[diesel(table_name = prefill_tokens)]
+pub struct PrefillToken {
+    pub token: String,
+    pub email: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable, Queryable)]
+#[diesel(table_name = clubs)]
+pub struct Club {
+    pub code: String,
+    pub name: String,
+    /// [`common::club::Club::search_text`]
+    pub search: String,
+}
+
+impl From<Club> for common::club::Club {
+    fn from(val: Club) -> Self {
+        common::club::Club {
+            code: val.code,
+            name: val.name,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = tournaments)]
+pub struct Tournament {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+    pub date: chrono::NaiveDate,
+    pub venue: String,
+    pub season_start: chrono::NaiveDate,
+    pub kind: String,
+    pub sessions: String,
+    pub classes: String,
+    pub prices: String,
+    pub registration_opens: Option<chrono::NaiveDateTime>,
+    pub registration_closes: Option<chrono::NaiveDateTime>,
+    pub archived_at: Option<chrono::NaiveDateTime>,
+    pub info_url: String,
+}
+
+/// Settings of a tournament. The archive state is only changed by archiving.
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = tournaments, treat_none_as_null = true)]
+pub struct InsertableTournament {
+    pub slug: String,
+    pub name: String,
+    pub date: chrono::NaiveDate,
+    pub venue: String,
+    pub season_start: chrono::NaiveDate,
+    pub kind: String,
+    pub sessions: String,
+    pub classes: String,
+    pub prices: String,
+    pub registration_opens: Option<chrono::NaiveDateTime>,
+    pub registration_closes: Option<chrono::NaiveDateTime>,
+    pub info_url: String,
 }
@@ -0,0 +1,216 @@
+use crate::app::AppState;
+use crate::tournament::{CurrentTournament, MailTournament};
+use crate::{db, db::DbConnection, error::*, models::PrefillToken, schema};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use common::archer::PastArcher;
+use common::bow_type::BowType;
+use common::gender::Gender;
+use common::line_data::PrefillRequest;
+use common::locale::Locale;
+use diesel::prelude::*;
+use lettre::message::{header::ContentType, Mailbox};
+use rand::{distributions::Alphanumeric, Rng};
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Mails a one-time link to fill in the archers registered with the mail address before.
+/// Always answers the same and right away, the link is looked up and mailed afterwards,
+/// so nobody learns which addresses registered.
+pub async fn request_prefill(
+    State(state): State<AppState>,
+    current: CurrentTournament,
+    Json(request): Json<PrefillRequest>,
+) -> Result<impl IntoResponse> {
+    if state.config.prefill.is_none() {
+        return Ok(StatusCode::NOT_FOUND);
+    }
+    tokio::spawn(async move {
+        if let Err(e) = send_prefill_link(&state, &request, &current).await {
+            log::error!("Couldn't send prefill mail to {}: {}", request.mail, e);
+        }
+    });
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Stores a prefill token and mails its link, if archers were registered with the mail address
+async fn send_prefill_link(
+    state: &AppState,
+    request: &PrefillRequest,
+    current: &CurrentTournament,
+) -> Result<()> {
+    let mail = request.mail.to_string();
+    let token = db::run(&state.pool, move |conn| -> Result<Option<String>> {
+        if past_archers(conn, &mail)?.is_empty() {
+            return Ok(None);
+        }
+        let prefill = PrefillToken {
+            token: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(TOKEN_LENGTH)
+                .map(char::from)
+                .collect(),
+            email: mail,
+            created_at: db::now(),
+        };
+        let token = prefill.token.clone();
+        diesel::insert_into(schema::prefill_tokens::table)
+            .values(prefill)
+            .execute(conn)?;
+        Ok(Some(token))
+    })
+    .await?;
+
+    match token {
+        Some(token) => send_prefill_mail(state, request, current, &token).await,
+        None => {
+            log::info!("No past archers registered by {}", request.mail);
+            Ok(())
+        }
+    }
+}
+
+/// Archers of the prefill link. The link is invalid afterwards.
+pub async fn get_prefill(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse> {
+    let timeout_hours = state
+        .config
+        .prefill
+        .as_ref()
+        .map_or(0, |c| c.link_timeout_hours);
+    let archers = db::run(
+        &state.pool,
+        move |conn| -> Result<Option<Vec<PastArcher>>> {
+            take_token(conn, &token, timeout_hours)?
+                .map(|prefill| past_archers(conn, &prefill.email))
+                .transpose()
+        },
+    )
+    .await?;
+    Ok(match archers {
+        Some(archers) => (StatusCode::OK, Json(archers)).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            "Der Link ist ungültig oder abgelaufen.",
+        )
+            .into_response(),
+    })
+}
+
+/// Latest registration of every archer registered with the mail address.
+/// Anonymized archers are left out.
+fn past_archers(connection: &mut DbConnection, mail: &str) -> Result<Vec<PastArcher>> {
+    use schema::{archers, registrations};
+    let rows: Vec<crate::models::Archer> = archers::table
+        .inner_join(registrations::table)
+        .filter(registrations::email.is_not_null())
+        .filter(db::lower(registrations::email.assume_not_null()).eq(mail.trim().to_lowercase()))
+        .order(archers::bib.desc())
+        .select(crate::models::Archer::as_select())
+        .load(connection)?;
+
+    let mut past: Vec<PastArcher> = Vec::new();
+    for archer in rows {
+        let Some(bow_type) = db::parse_enum_code::<BowType>(&archer.bow_type) else {
+            continue;
+        };
+        let archer = PastArcher {
+            first_name: archer.first_name,
+            last_name: archer.last_name,
+            date_of_birth: archer.date_of_birth,
+            bow_type,
+            gender: archer
+                .gender
+                .and_then(|gender| db::parse_enum_code::<Gender>(&gender)),
+        };
+        if !archer.first_name.is_empty()
+            && !past.iter().any(|p| {
+                p.first_name.eq_ignore_ascii_case(&archer.first_name)
+                    && p.last_name.eq_ignore_ascii_case(&archer.last_name)
+                    && p.date_of_birth == archer.date_of_birth
+            })
+        {
+            past.push(archer);
+        }
+    }
+    Ok(past)
+}
+
+/// Removes and returns a prefill token if it's not expired yet. Expired tokens are purged.
+fn take_token(
+    connection: &mut DbConnection,
+    token: &str,
+    timeout_hours: u32,
+) -> Result<Option<PrefillToken>> {
+    use schema::prefill_tokens;
+    let cutoff = db::now() - chrono::Duration::hours(timeout_hours.into());
+    connection.transaction(|conn| {
+        diesel::delete(prefill_tokens::table.filter(prefill_tokens::created_at.lt(cutoff)))
+            .execute(conn)?;
+        let prefill = prefill_tokens::table
+            .find(token)
+            .first::<PrefillToken>(conn)
+            .optional()?;
+        if prefill.is_some() {
+            diesel::delete(prefill_tokens::table.find(token)).execute(conn)?;
+        }
+        Ok(prefill)
+    })
+}
+
+#[derive(Debug, serde::Serialize)]
+struct PrefillMailData {
+    tournament: MailTournament,
+    prefill_link: String,
+    valid_hours: u32,
+}
+
+async fn send_prefill_mail(
+    state: &AppState,
+    request: &PrefillRequest,
+    current: &CurrentTournament,
+    token: &str,
+) -> Result<()> {
+    let (base_url, subject, valid_hours) = {
+        let prefill = state
+            .config
+            .prefill
+            .as_ref()
+            .expect("Prefill mails are only sent with prefill enabled");
+        (
+            prefill.base_url.clone(),
+            prefill.subject.clone(),
+            prefill.link_timeout_hours,
+        )
+    };
+    let mail_data = PrefillMailData {
+        tournament: MailTournament::new(&current.tournament, request.locale),
+        prefill_link: format!(
+            "{}/t/{}?prefill={}",
+            base_url.trim_end_matches('/'),
+            current.tournament.slug,
+            token
+        ),
+        valid_hours,
+    };
+    let email = crate::mail::message_builder(&state.config.mail_message)?
+        .to(Mailbox::new(None, request.mail.as_str().parse()?))
+        .header(ContentType::TEXT_PLAIN)
+        .subject(subject)
+        .body(state.templates.render(
+            match request.locale {
+                Locale::En => "prefill_mail_en",
+                Locale::De => "prefill_mail",
+            },
+            &mail_data,
+        )?)
+        .unwrap();
+
+    state.mailer.send(email).await
+}
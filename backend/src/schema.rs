@@ -1,56 +1,120 @@
 // @generated automatically by Diesel CLI.
 
 diesel::table! {
-    archer_additions (bib) {
+    archers (bib) {
         bib -> Integer,
+        registration_id -> Integer,
+        team_id -> Nullable<Integer>,
+        session -> Integer,
+        first_name -> Text,
+        last_name -> Text,
+        date_of_birth -> Date,
+        gender -> Nullable<Text>,
+        bow_type -> Text,
+        class -> Text,
+        target_face -> Text,
+        club_code -> Nullable<Text>,
+        club_name -> Text,
+        paid -> Bool,
+        individual_final -> Bool,
+        team_final -> Bool,
+        mixed_team_final -> Bool,
+    }
+}
+
+diesel::table! {
+    clubs (code) {
+        code -> Text,
+        name -> Text,
+        search -> Text,
+    }
+}
+
+diesel::table! {
+    mail_queue (id) {
+        id -> Integer,
+        recipient -> Text,
+        subject -> Text,
+        body -> Text,
+        created_at -> Timestamp,
+        sent_at -> Nullable<Timestamp>,
+        attempts -> Integer,
+        last_error -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    pending_registrations (token) {
+        token -> Text,
+        payload -> Text,
+        created_at -> Timestamp,
+        tournament_id -> Integer,
+    }
+}
+
+diesel::table! {
+    prefill_tokens (token) {
+        token -> Text,
+        email -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    registrations (id) {
+        id -> Integer,
+        tournament_id -> Integer,
+        name -> Text,
         email -> Nullable<Text>,
-        comment -> Nullable<Text>,
-        #[sql_name = "target face"]
-        target_face -> Nullable<Text>,
+        club -> Text,
+        comment -> Text,
+        locale -> Text,
+        created_at -> Timestamp,
     }
 }
 
 diesel::table! {
-    archers (bib) {
-        bib -> Integer,
-        session -> Integer,
-        division -> Text,
+    teams (id) {
+        id -> Integer,
+        registration_id -> Integer,
+        name -> Text,
         class -> Text,
-        target -> Text,
-        #[sql_name = "individual qualification"]
-        individual_qualification -> Integer,
-        #[sql_name = "team qualification"]
-        team_qualification -> Integer,
-        #[sql_name = "individual final"]
-        individual_final -> Integer,
-        #[sql_name = "team final"]
-        team_final -> Integer,
-        #[sql_name = "mixed team final"]
-        mixed_team_final -> Integer,
-        #[sql_name = "last name"]
-        last_name -> Text,
-        #[sql_name = "first name"]
-        first_name -> Text,
-        gender -> Nullable<Integer>,
-        #[sql_name = "country code"]
-        country_code -> Text,
-        #[sql_name = "country name"]
-        country_name -> Text,
-        #[sql_name = "date of birth"]
-        date_of_birth -> Text,
-        subclass -> Nullable<Text>,
-        #[sql_name = "country code 2"]
-        country_code_2 -> Nullable<Text>,
-        #[sql_name = "country name 2"]
-        country_name_2 -> Nullable<Text>,
-        #[sql_name = "country code 3"]
-        country_code_3 -> Nullable<Text>,
-        #[sql_name = "country name 3"]
-        country_name_3 -> Nullable<Text>,
     }
 }
 
+diesel::table! {
+    tournaments (id) {
+        id -> Integer,
+        slug -> Text,
+        name -> Text,
+        date -> Date,
+        venue -> Text,
+        season_start -> Date,
+        kind -> Text,
+        sessions -> Text,
+        classes -> Text,
+        prices -> Text,
+        registration_opens -> Nullable<Timestamp>,
+        registration_closes -> Nullable<Timestamp>,
+        archived_at -> Nullable<Timestamp>,
+        info_url -> Text,
+    }
+}
+
+diesel::joinable!(archers -> clubs (club_code));
+diesel::joinable!(archers -> registrations (registration_id));
+diesel::joinable!(archers -> teams (team_id));
+diesel::joinable!(pending_registrations -> tournaments (tournament_id));
+diesel::joinable!(registrations -> tournaments (tournament_id));
+diesel::joinable!(teams -> registrations (registration_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
-    archer_additions,
     archers,
+    clubs,
+    mail_queue,
+    pending_registrations,
+    prefill_tokens,
+    registrations,
+    teams,
+    tournaments,
 );
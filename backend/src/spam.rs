@@ -0,0 +1,295 @@
+use crate::config::{Config, SpamProtectionConfig};
+use axum::{
+    body::{Body, Bytes},
+    extract::{ConnectInfo, FromRequest, MatchedPath, State},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use common::line_data::CreateArchersPayload;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+use subtle::ConstantTimeEq;
+
+/// Sliding window rate limiter, counting requests per key
+pub struct RateLimiter {
+    window: Duration,
+    hits: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a request for `key` and returns whether it is within `max` requests per window
+    pub fn check(&self, key: &str, max: usize) -> bool {
+        self.check_at(key, max, Instant::now())
+    }
+
+    /// Reserves one of the `max` requests per window of `key`, unless they are used up.
+    /// The request counts until it leaves the window or is [released](Self::release).
+    pub fn try_acquire(&self, key: &str, max: usize) -> Option<Instant> {
+        let now = Instant::now();
+        self.check_at(key, max, now).then_some(now)
+    }
+
+    /// Takes back a request reserved with [`try_acquire`](Self::try_acquire)
+    pub fn release(&self, key: &str, acquired: Instant) {
+        let mut hits = self.hits.lock().unwrap();
+        if let Some(times) = hits.get_mut(key) {
+            if let Some(index) = times.iter().position(|&time| time == acquired) {
+                times.remove(index);
+            }
+        }
+    }
+
+    fn check_at(&self, key: &str, max: usize, now: Instant) -> bool {
+        let mut hits = self.hits_at(now);
+        let times = hits.entry(key.to_string()).or_default();
+        if times.len() >= max {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+
+    /// Requests per key, without the ones which left the window
+    fn hits_at(&self, now: Instant) -> MutexGuard<'_, HashMap<String, VecDeque<Instant>>> {
+        let mut hits = self.hits.lock().unwrap();
+        hits.retain(|_, times| {
+            while times
+                .front()
+                .is_some_and(|&t| now.duration_since(t) >= self.window)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+        hits
+    }
+}
+
+/// Middleware limiting the body size and the requests per client ip and per registrator mail
+/// address. Every route has its own limits. Every request counts for the ip, but only accepted
+/// ones for the mail address, so a registration sent again after a duplicate warning counts once.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    State(config): State<Arc<Config>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let config = &config.spam_protection;
+    let ip = config
+        .trust_proxy_headers
+        .then(|| request.headers().get("X-Real-IP"))
+        .flatten()
+        .and_then(|ip| ip.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| peer.ip().to_string());
+    let route = request.extensions().get::<MatchedPath>().map_or_else(
+        || request.uri().path().to_string(),
+        |path| path.as_str().to_string(),
+    );
+
+    let (parts, body) = request.into_parts();
+    let limited_body = Request::new(http_body::Limited::new(body, config.max_body_bytes));
+    let body = match Bytes::from_request(limited_body, &()).await {
+        Ok(body) => body,
+        Err(rejection) => {
+            log::warn!("Rejected request to {} from {}: {}", route, ip, rejection);
+            return rejection.into_response();
+        }
+    };
+    let mail = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|payload| payload.get("mail")?.as_str().map(str::to_lowercase));
+
+    if !limiter.check(&format!("ip:{route}:{ip}"), config.max_registrations_per_ip) {
+        log::warn!(
+            "Rejected request to {} from {}: too many requests",
+            route,
+            ip
+        );
+        return too_many_requests();
+    }
+    // Reserved before the request runs, so concurrent requests can't exceed the limit
+    let mail_reservation = match mail {
+        Some(mail) => {
+            let key = format!("mail:{route}:{mail}");
+            match limiter.try_acquire(&key, config.max_registrations_per_mail) {
+                Some(acquired) => Some((key, acquired)),
+                None => {
+                    log::warn!(
+                        "Rejected request to {} from {}: too many requests for {}",
+                        route,
+                        ip,
+                        mail
+                    );
+                    return too_many_requests();
+                }
+            }
+        }
+        None => None,
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if let Some((key, acquired)) = mail_reservation {
+        if !response.status().is_success() {
+            limiter.release(&key, acquired);
+        }
+    }
+    response
+}
+
+fn too_many_requests() -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        "Zu viele Anmeldungen. Bitte versuche es später erneut.",
+    )
+        .into_response()
+}
+
+/// Signs the time a form was opened, so clients can't make up how long they took to fill it.
+/// The key is made at startup, forms opened before a restart have to be reloaded.
+pub struct FormSigner {
+    /// Padded to the block size of SHA-256
+    key: [u8; 64],
+}
+
+impl FormSigner {
+    pub fn generate() -> Self {
+        let mut key = [0; 64];
+        rand::thread_rng().fill_bytes(&mut key[..32]);
+        Self { key }
+    }
+
+    /// Stamp of the current time
+    pub fn issue(&self) -> String {
+        self.issue_at(chrono::Utc::now().timestamp())
+    }
+
+    /// Seconds since the stamp was issued, `None` if it wasn't issued with this key
+    pub fn elapsed_seconds(&self, stamp: &str) -> Option<i64> {
+        self.elapsed_at(stamp, chrono::Utc::now().timestamp())
+    }
+
+    fn issue_at(&self, time: i64) -> String {
+        format!("{}.{}", time, self.signature(time))
+    }
+
+    fn elapsed_at(&self, stamp: &str, now: i64) -> Option<i64> {
+        let (time, signature) = stamp.split_once('.')?;
+        let time: i64 = time.parse().ok()?;
+        bool::from(self.signature(time).as_bytes().ct_eq(signature.as_bytes()))
+            .then_some(now - time)
+    }
+
+    fn signature(&self, time: i64) -> String {
+        self.hmac(time.to_string().as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// HMAC-SHA256 of RFC 2104
+    fn hmac(&self, message: &[u8]) -> [u8; 32] {
+        let pad = |byte: u8| self.key.map(|k| k ^ byte);
+        let inner = Sha256::new()
+            .chain_update(pad(0x36))
+            .chain_update(message)
+            .finalize();
+        Sha256::new()
+            .chain_update(pad(0x5c))
+            .chain_update(inner)
+            .finalize()
+            .into()
+    }
+}
+
+/// Stamp for a newly opened registration form, to be sent back with the registration
+pub async fn get_form_stamp(State(signer): State<Arc<FormSigner>>) -> impl IntoResponse {
+    Json(signer.issue())
+}
+
+pub enum SubmissionCheck {
+    Ok,
+    /// Honeypot field was filled, most likely by a bot
+    Honeypot,
+    /// Form was submitted faster than a human could fill it, after the seconds given
+    TooFast(i64),
+    /// The form stamp is missing or wasn't issued by this server
+    InvalidStamp,
+}
+
+pub fn check_submission(
+    payload: &CreateArchersPayload,
+    config: &SpamProtectionConfig,
+    signer: &FormSigner,
+) -> SubmissionCheck {
+    if !payload.website.is_empty() {
+        return SubmissionCheck::Honeypot;
+    }
+    match signer.elapsed_seconds(&payload.form_stamp) {
+        None => SubmissionCheck::InvalidStamp,
+        Some(seconds) if seconds < i64::from(config.min_fill_seconds) => {
+            SubmissionCheck::TooFast(seconds)
+        }
+        Some(_) => SubmissionCheck::Ok,
+    }
+}
+
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::new(Duration::from_secs(60));
+    let start = Instant::now();
+    assert!(limiter.check_at("a", 2, start));
+    assert!(limiter.check_at("a", 2, start + Duration::from_secs(10)));
+    assert!(!limiter.check_at("a", 2, start + Duration::from_secs(20)));
+    assert!(limiter.check_at("b", 2, start + Duration::from_secs(20)));
+    assert!(limiter.check_at("a", 2, start + Duration::from_secs(61)));
+    assert!(!limiter.check_at("a", 2, start + Duration::from_secs(62)));
+
+    // A released request doesn't count
+    let limiter = RateLimiter::new(Duration::from_secs(60));
+    let acquired = limiter.try_acquire("a", 1).unwrap();
+    assert!(limiter.try_acquire("a", 1).is_none());
+    limiter.release("a", acquired);
+    assert!(limiter.try_acquire("a", 1).is_some());
+    assert!(limiter.try_acquire("a", 1).is_none());
+}
+
+#[test]
+fn test_form_stamp() {
+    let signer = FormSigner::generate();
+    let stamp = signer.issue_at(1000);
+    assert_eq!(signer.elapsed_at(&stamp, 1007), Some(7));
+    assert_eq!(
+        signer.elapsed_at(&stamp.replacen("1000", "990", 1), 1007),
+        None
+    );
+    assert_eq!(signer.elapsed_at("", 1007), None);
+    assert_eq!(FormSigner::generate().elapsed_at(&stamp, 1007), None);
+
+    // Test case 2 of RFC 4231
+    let mut key = [0; 64];
+    key[..4].copy_from_slice(b"Jefe");
+    let hmac = FormSigner { key }.hmac(b"what do ya want for nothing?");
+    assert_eq!(
+        hmac.iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>(),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
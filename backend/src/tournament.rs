@@ -0,0 +1,211 @@
+use crate::{admin::AdminAuth, db, db::DbConnection, error::*, models, schema::tournaments};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path, State},
+    http::{request::Parts, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use common::locale::Locale;
+use common::tournament::Tournament;
+use diesel::prelude::*;
+use serde::Serialize;
+
+/// Extractor of the tournament addressed by the `slug` path parameter
+#[derive(Clone)]
+pub struct CurrentTournament {
+    pub id: i32,
+    pub tournament: Tournament,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentTournament
+where
+    db::Pool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = axum::response::Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let Path(slug) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let pool = db::Pool::from_ref(state);
+        match db::run(&pool, move |conn| find(conn, &slug)).await {
+            Ok(Some((id, tournament))) => Ok(CurrentTournament { id, tournament }),
+            Ok(None) => Err((StatusCode::NOT_FOUND, "Turnier nicht gefunden").into_response()),
+            Err(e) => Err(e.into_response()),
+        }
+    }
+}
+
+pub async fn get_tournament(tournament: CurrentTournament) -> impl IntoResponse {
+    Json(tournament.tournament)
+}
+
+pub async fn list_tournaments(State(pool): State<db::Pool>) -> Result<impl IntoResponse> {
+    let tournaments = db::run(&pool, all).await?;
+    Ok(Json(
+        tournaments
+            .into_iter()
+            .map(|(_, tournament)| tournament)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// Creates or updates the tournament with the slug of the path
+pub async fn put_tournament(
+    _: AdminAuth,
+    State(pool): State<db::Pool>,
+    Path(slug): Path<String>,
+    Json(tournament): Json<Tournament>,
+) -> Result<impl IntoResponse> {
+    let tournament = Tournament { slug, ..tournament };
+    db::run(&pool, move |conn| save(conn, &tournament)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub fn find(connection: &mut DbConnection, slug: &str) -> Result<Option<(i32, Tournament)>> {
+    tournaments::table
+        .filter(tournaments::slug.eq(slug))
+        .select(models::Tournament::as_select())
+        .first(connection)
+        .optional()?
+        .map(|t| Ok((t.id, t.try_into()?)))
+        .transpose()
+}
+
+pub fn by_id(connection: &mut DbConnection, id: i32) -> Result<Tournament> {
+    tournaments::table
+        .find(id)
+        .select(models::Tournament::as_select())
+        .first(connection)?
+        .try_into()
+}
+
+/// All tournaments, the latest first
+pub fn all(connection: &mut DbConnection) -> Result<Vec<(i32, Tournament)>> {
+    tournaments::table
+        .order(tournaments::date.desc())
+        .select(models::Tournament::as_select())
+        .load(connection)?
+        .into_iter()
+        .map(|t| Ok((t.id, t.try_into()?)))
+        .collect()
+}
+
+/// Inserts the tournament or updates the one with the same slug. Archived tournaments are refused.
+pub fn save(connection: &mut DbConnection, tournament: &Tournament) -> Result<()> {
+    let row = models::InsertableTournament::from(tournament);
+    connection.transaction(|conn| {
+        crate::archive::refuse_archived_tournament(conn, &tournament.slug)?;
+        diesel::insert_into(tournaments::table)
+            .values(&row)
+            .on_conflict(tournaments::slug)
+            .do_update()
+            .set(&row)
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+impl From<&Tournament> for models::InsertableTournament {
+    fn from(val: &Tournament) -> Self {
+        models::InsertableTournament {
+            slug: val.slug.clone(),
+            name: val.name.clone(),
+            date: val.date,
+            venue: val.venue.clone(),
+            season_start: val.season_start,
+            kind: crate::db::enum_code(&val.kind),
+            sessions: serde_json::to_string(&val.sessions).unwrap(),
+            classes: serde_json::to_string(&val.classes).unwrap(),
+            prices: serde_json::to_string(&val.prices).unwrap(),
+            registration_opens: val.registration_opens,
+            registration_closes: val.registration_closes,
+            info_url: val.info_url.clone(),
+        }
+    }
+}
+
+impl TryFrom<models::Tournament> for Tournament {
+    type Error = Error;
+
+    fn try_from(val: models::Tournament) -> Result<Self> {
+        let invalid = |field: &str, e: &dyn std::fmt::Display| {
+            Error::DataError(format!(
+                "Invalid {} of tournament {}: {}",
+                field, val.slug, e
+            ))
+        };
+        Ok(Tournament {
+            date: val.date,
+            season_start: val.season_start,
+            kind: crate::db::parse_enum_code(&val.kind)
+                .ok_or_else(|| invalid("kind", &val.kind))?,
+            sessions: serde_json::from_str(&val.sessions).map_err(|e| invalid("sessions", &e))?,
+            classes: serde_json::from_str(&val.classes).map_err(|e| invalid("classes", &e))?,
+            prices: serde_json::from_str(&val.prices).map_err(|e| invalid("prices", &e))?,
+            registration_opens: val.registration_opens,
+            registration_closes: val.registration_closes,
+            archived_at: val.archived_at,
+            slug: val.slug,
+            name: val.name,
+            venue: val.venue,
+            info_url: val.info_url,
+        })
+    }
+}
+
+/// Tournament data for the mail templates
+#[derive(Debug, Serialize)]
+pub struct MailTournament {
+    pub slug: String,
+    pub name: String,
+    pub date: String,
+    pub venue: String,
+    pub info_url: String,
+}
+
+impl MailTournament {
+    pub fn new(tournament: &Tournament, locale: Locale) -> Self {
+        MailTournament {
+            slug: tournament.slug.clone(),
+            name: tournament.name.clone(),
+            date: match locale {
+                Locale::En => tournament.date.format("%B %-d, %Y"),
+                Locale::De => tournament.date.format("%d.%m.%Y"),
+            }
+            .to_string(),
+            venue: tournament.venue.clone(),
+            info_url: tournament.info_url.clone(),
+        }
+    }
+}
+
+#[test]
+fn test_save_and_find() {
+    let Some(mut connection) = crate::db::test_connection() else {
+        return;
+    };
+    let tournament = Tournament {
+        slug: "test".into(),
+        name: "Test".into(),
+        ..Default::default()
+    };
+    save(&mut connection, &tournament).unwrap();
+    let moved = Tournament {
+        venue: "Halle".into(),
+        ..tournament
+    };
+    save(&mut connection, &moved).unwrap();
+    let (id, found) = find(&mut connection, "test").unwrap().unwrap();
+    assert_eq!(found, moved);
+    assert_eq!(by_id(&mut connection, id).unwrap(), moved);
+    assert!(find(&mut connection, "missing").unwrap().is_none());
+    // The migrated indoor tournament of 2025 and the new one
+    assert_eq!(all(&mut connection).unwrap().len(), 2);
+}
@@ -1,6 +1,6 @@
 Hallo {{name}},
 
-vielen Dank für die Meldung für {{club}} zum PSV Indoor Turnier 2025 am 23.Februar.
+vielen Dank für die Meldung für {{club}} zum {{tournament.name}} am {{tournament.date}}.
 
 angegebener Kommentar:
 {{comment}}
@@ -14,17 +14,28 @@ Gruppe: {{this.session}}
 Bogenart: {{this.division}}
 Klasse: {{this.class}} ({{this.price}})
 Scheibe: {{this.target}}
+Finale: {{this.finals}}
+
+{{/each}}
+{{#each teams}}
+Mannschaft: {{this.name}}
+Klasse: {{this.class}} ({{this.price}})
+Schützen: {{#each this.members}}{{this}}{{#unless @last}}, {{/unless}}{{/each}}
 
 {{/each}}
 
 Wir bitten um eine baldige Überweisung der Startgebühr.
 Falls ihr euch auf die Warteliste angemeldet habt, bitte erst nach Zusage bezahlen.
 Betrag: {{total_price}}
-IBAN: DE97 1001 1001 2620 7749 14
-BIC: NTSBDEB1XXX
-Verwendungszweck: Indoor25 - {{club}}
-
-Weitere Informationen auf https://bogen-psv.de/indoor.html
+{{#if payment.iban}}
+IBAN: {{payment.iban}}
+BIC: {{payment.bic}}
+{{/if}}
+Verwendungszweck: {{tournament.slug}} - {{club}}
+{{#if tournament.info_url}}
+
+Weitere Informationen auf {{tournament.info_url}}
+{{/if}}
 
 Viele Grüße und Alle ins Gold
 Tobias Edlböck
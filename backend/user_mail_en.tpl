@@ -1,6 +1,6 @@
 Hello {{name}},
 
-Thanks for your registration of {{club}} at PSV's Indoor tournament on Febraury, 23rd 2025.
+Thanks for your registration of {{club}} at {{tournament.name}} on {{tournament.date}}.
 
 given comment:
 {{comment}}
@@ -14,17 +14,28 @@ Session: {{this.session}}
 Bow type: {{this.division}}
 Class: {{this.class}} ({{this.price}})
 Target face: {{this.target}}
+Finals: {{this.finals}}
+
+{{/each}}
+{{#each teams}}
+Team: {{this.name}}
+Class: {{this.class}} ({{this.price}})
+Archers: {{#each this.members}}{{this}}{{#unless @last}}, {{/unless}}{{/each}}
 
 {{/each}}
 
 Please transfer the entry fees as soon as possible to our bank account.
 If you registered for the waiting list, please do not pay before your place was confirmed.
 Total sum: {{total_price}}
-IBAN: DE97 1001 1001 2620 7749 14
-BIC: NTSBDEB1XXX
-Reason for payment: Indoor25 - {{club}}
-
-Additional info can be found at https://bogen-psv.de/indoor.html
+{{#if payment.iban}}
+IBAN: {{payment.iban}}
+BIC: {{payment.bic}}
+{{/if}}
+Reason for payment: {{tournament.slug}} - {{club}}
+{{#if tournament.info_url}}
+
+Additional info can be found at {{tournament.info_url}}
+{{/if}}
 
 Kind Regards
 Tobias Edlböck
@@ -1,8 +1,17 @@
+use std::fmt::Display;
+
 use chrono::NaiveDate;
 use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
 
-use crate::{class::Class, target_face::TargetFace};
+use crate::{
+    bow_type::BowType,
+    class::{Class, Rules},
+    duplicate::ArcherIdentity,
+    gender::Gender,
+    target_face::TargetFace,
+    tournament::Tournament,
+};
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Archer {
@@ -15,6 +24,65 @@ pub struct Archer {
     date_of_birth: NaiveDate,
     class: Class,
     target_face: TargetFace,
+    #[serde(default)]
+    gender: Option<Gender>,
+    #[serde(default)]
+    finals: Finals,
+}
+
+/// Finals an archer signs up for
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Finals {
+    pub individual: bool,
+    /// Only possible for members of a team
+    pub team: bool,
+    pub mixed_team: bool,
+}
+
+impl Finals {
+    pub fn any(&self) -> bool {
+        self.individual || self.team || self.mixed_team
+    }
+}
+
+/// Archer of a past registration, offered to fill in the form again.
+/// The class is left out as it's recomputed for the new season.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PastArcher {
+    pub first_name: String,
+    pub last_name: String,
+    pub date_of_birth: NaiveDate,
+    pub bow_type: BowType,
+    pub gender: Option<Gender>,
+}
+
+/// Why an archer can't start at a tournament
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArcherError {
+    /// The class is unknown, not offered or not open to the date of birth
+    Class,
+    TargetFace,
+    /// The class is open to any gender and none is given
+    MissingGender,
+    /// The given gender is another one than the one of the class
+    Gender,
+    /// The class has no finals
+    Finals,
+    Session,
+}
+
+impl std::error::Error for ArcherError {}
+impl Display for ArcherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArcherError::Class => write!(f, "Class is not allowed"),
+            ArcherError::TargetFace => write!(f, "Target face is not allowed in the class"),
+            ArcherError::MissingGender => write!(f, "Gender is missing"),
+            ArcherError::Gender => write!(f, "Gender doesn't match the class"),
+            ArcherError::Finals => write!(f, "Class has no finals"),
+            ArcherError::Session => write!(f, "Session is unknown"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -28,6 +96,7 @@ pub struct RegisteredArcher {
 }
 
 impl Archer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         first_name: String,
         last_name: String,
@@ -38,24 +107,12 @@ impl Archer {
         comment: String,
         club: String,
         session: u8,
-    ) -> Result<Self, ()> {
-        let mut allowed_classes = Class::allowed_classes(crate::bow_type::BowType::Recurve, dob);
-        allowed_classes.append(&mut Class::allowed_classes(
-            crate::bow_type::BowType::Compound,
-            dob,
-        ));
-        allowed_classes.append(&mut Class::allowed_classes(
-            crate::bow_type::BowType::Barebow,
-            dob,
-        ));
-        let allowed_classes: Vec<_> = allowed_classes.into_iter().map(|(cls, _)| cls).collect();
-        if !allowed_classes.contains(&cls) {
-            return Err(());
-        }
-        if !TargetFace::for_cls(cls).contains(&target_face) {
-            return Err(());
-        }
-        Ok(Self {
+        gender: Option<Gender>,
+        finals: Finals,
+        rules: &Rules,
+        tournament: &Tournament,
+    ) -> Result<Self, ArcherError> {
+        let mut archer = Self {
             first_name,
             last_name,
             mail,
@@ -65,17 +122,74 @@ impl Archer {
             comment,
             club,
             session,
-        })
+            gender,
+            finals,
+        };
+        archer.validate(rules, tournament)?;
+        archer.gender = rules
+            .class(&archer.class)
+            .and_then(|definition| definition.gender)
+            .or(gender);
+        Ok(archer)
+    }
+
+    /// Checks the archer against the rules of the tournament.
+    /// Archers sent by the form have to be checked again, as the form may be outdated or forged.
+    pub fn validate(&self, rules: &Rules, tournament: &Tournament) -> Result<(), ArcherError> {
+        if !rules
+            .all_allowed_classes(self.date_of_birth, tournament)
+            .contains(&self.class)
+        {
+            return Err(ArcherError::Class);
+        }
+        if !rules
+            .target_faces(&self.class, tournament)
+            .contains(&self.target_face)
+        {
+            return Err(ArcherError::TargetFace);
+        }
+        let Some(definition) = rules.class(&self.class) else {
+            return Err(ArcherError::Class);
+        };
+        match (definition.gender, self.gender) {
+            (Some(cls_gender), Some(gender)) if cls_gender != gender => {
+                return Err(ArcherError::Gender)
+            }
+            (None, None) => return Err(ArcherError::MissingGender),
+            _ => (),
+        }
+        if self.finals.any() && !definition.finals {
+            return Err(ArcherError::Finals);
+        }
+        if !tournament.has_session(self.session) {
+            return Err(ArcherError::Session);
+        }
+        Ok(())
     }
     pub fn date_of_birth(&self) -> NaiveDate {
         self.date_of_birth
     }
-    pub fn class(&self) -> Class {
-        self.class
+    pub fn class(&self) -> &Class {
+        &self.class
     }
     pub fn target_face(&self) -> TargetFace {
         self.target_face
     }
+    /// Gender implied by the class or given at registration
+    pub fn gender(&self) -> Option<Gender> {
+        self.gender
+    }
+    pub fn finals(&self) -> Finals {
+        self.finals
+    }
+    pub fn identity(&self) -> ArcherIdentity {
+        ArcherIdentity {
+            first_name: self.first_name.clone(),
+            last_name: self.last_name.clone(),
+            date_of_birth: self.date_of_birth,
+            club: self.club.clone(),
+        }
+    }
 }
 
 impl PartialOrd for Archer {
@@ -87,3 +201,82 @@ impl PartialOrd for Archer {
             .or_else(|| self.date_of_birth.partial_cmp(&self.date_of_birth))
     }
 }
+
+#[test]
+fn test_gender_required_for_mixed_classes() {
+    use std::str::FromStr;
+    let rules = Rules::default();
+    let tournament = Tournament {
+        season_start: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+        ..Default::default()
+    };
+    let archer = |cls: &str, gender: Option<Gender>| {
+        let cls = Class::new(cls);
+        Archer::new(
+            "Foo".into(),
+            "Bar".into(),
+            EmailAddress::from_str("foo@bar.com").unwrap(),
+            NaiveDate::from_ymd_opt(2015, 1, 1).unwrap(),
+            cls.clone(),
+            rules.target_faces(&cls, &tournament)[0],
+            "".into(),
+            "PSV".into(),
+            0,
+            gender,
+            Finals::default(),
+            &rules,
+            &tournament,
+        )
+    };
+    assert_eq!(archer("BU15", None), Err(ArcherError::MissingGender));
+    assert_eq!(
+        archer("BU15", Some(Gender::Female)).unwrap().gender(),
+        Some(Gender::Female)
+    );
+    assert_eq!(
+        archer("RU11W", None).unwrap().gender(),
+        Some(Gender::Female)
+    );
+    assert_eq!(
+        archer("RU11W", Some(Gender::Male)),
+        Err(ArcherError::Gender)
+    );
+}
+
+#[test]
+fn test_finals_depend_on_class() {
+    use std::str::FromStr;
+    let rules = Rules::default();
+    let tournament = Tournament {
+        season_start: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+        ..Default::default()
+    };
+    let archer = |cls: &str, year: i32, finals: Finals| {
+        let cls = Class::new(cls);
+        Archer::new(
+            "Foo".into(),
+            "Bar".into(),
+            EmailAddress::from_str("foo@bar.com").unwrap(),
+            NaiveDate::from_ymd_opt(year, 1, 1).unwrap(),
+            cls.clone(),
+            rules.target_faces(&cls, &tournament)[0],
+            "".into(),
+            "PSV".into(),
+            0,
+            None,
+            finals,
+            &rules,
+            &tournament,
+        )
+    };
+    let individual = Finals {
+        individual: true,
+        ..Default::default()
+    };
+    assert!(archer("RU11W", 2016, Finals::default()).is_ok());
+    assert_eq!(archer("RU11W", 2016, individual), Err(ArcherError::Finals));
+    assert_eq!(
+        archer("RU13W", 2013, individual).unwrap().finals(),
+        individual
+    );
+}
@@ -1,10 +1,16 @@
+use crate::locale::Locale;
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, strum::EnumIter)]
 pub enum BowType {
+    #[default]
     Recurve,
     Compound,
     Barebow,
+    Longbow,
+    Traditional,
+    Instinctive,
 }
 
 impl BowType {
@@ -17,10 +23,32 @@ impl BowType {
     pub fn is_barebow(&self) -> bool {
         matches!(self, Self::Barebow)
     }
-}
-
-impl Default for BowType {
-    fn default() -> Self {
-        Self::Recurve
+    /// Division code used by Ianseo
+    pub fn ianseo_division(&self) -> &'static str {
+        match self {
+            Self::Recurve => "R",
+            Self::Compound => "C",
+            Self::Barebow => "B",
+            Self::Longbow => "L",
+            Self::Traditional => "T",
+            Self::Instinctive => "I",
+        }
+    }
+    pub fn from_ianseo_division(division: &str) -> Option<Self> {
+        Self::iter().find(|bow_type| bow_type.ianseo_division() == division)
+    }
+    pub fn name(&self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Self::Recurve, _) => "Recurve",
+            (Self::Compound, _) => "Compound",
+            (Self::Barebow, Locale::En) => "Barebow",
+            (Self::Barebow, Locale::De) => "Blank",
+            (Self::Longbow, Locale::En) => "Longbow",
+            (Self::Longbow, Locale::De) => "Langbogen",
+            (Self::Traditional, Locale::En) => "Traditional",
+            (Self::Traditional, Locale::De) => "Traditionell",
+            (Self::Instinctive, Locale::En) => "Instinctive",
+            (Self::Instinctive, Locale::De) => "Instinktiv",
+        }
     }
 }
@@ -1,254 +1,250 @@
-use std::{fmt::Display, str::FromStr};
+use std::fmt::Display;
 
-use crate::{bow_type::BowType, locale::Locale};
+use crate::{
+    bow_type::BowType,
+    gender::Gender,
+    locale::Locale,
+    target_face::{TargetFace, TargetFaces},
+    team::TeamClassDefinition,
+    tournament::Tournament,
+};
 use chrono::{Months, NaiveDate};
 use itertools::Itertools;
-use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use strum::{Display, IntoEnumIterator};
+use strum::IntoEnumIterator;
 
-lazy_static! {
-    pub static ref SEASON_START: NaiveDate = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+/// Rule file used if no other one is configured
+pub const DEFAULT_RULES: &str = include_str!("../class_rules.toml");
+
+/// Code of a class, e.g. `RUE20M`. Its properties are defined by the [`Rules`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, PartialOrd, Ord, Hash)]
+#[serde(transparent)]
+pub struct Class(String);
+
+impl Class {
+    pub fn new(code: impl Into<String>) -> Self {
+        Self(code.into())
+    }
+    pub fn code(&self) -> &str {
+        &self.0
+    }
 }
 
-#[derive(
-    Debug,
-    Clone,
-    Copy,
-    PartialEq,
-    Eq,
-    Serialize,
-    Deserialize,
-    strum::EnumIter,
-    PartialOrd,
-    Hash,
-    Display,
-)]
-pub enum Class {
-    RUE20M,
-    RUE20W,
-    RU15M,
-    RU15W,
-    RU13M,
-    RU13W,
-    RU11M,
-    RU11W,
-    RU18M,
-    RU18W,
-    RU21M,
-    RU21W,
-    RUE49M,
-    RUE49W,
-    RUE65M,
-    RUE65W,
-    BUE20M,
-    BUE20W,
-    BU15,
-    BU21,
-    CUE20M,
-    CUE20W,
-    CU15,
-    CU21,
-    CUE49M,
-    CUE49W,
-    CUE65M,
-    CUE65W,
+impl Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
-impl Class {
-    pub fn name(&self, locale: Locale) -> &'static str {
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LocalizedName {
+    pub en: String,
+    pub de: String,
+}
+
+impl LocalizedName {
+    pub fn get(&self, locale: Locale) -> &str {
         match locale {
-            Locale::En => match self {
-                Class::RUE20M => "Recurve Men",
-                Class::RUE20W => "Recurve Women",
-                Class::RU15M => "Recurve 13-14 male",
-                Class::RU15W => "Recurve 13-14 female",
-                Class::RU13M => "Recurve 11-12 male",
-                Class::RU13W => "Recurve 11-12 female",
-                Class::RU11M => "Recurve 1-10 male",
-                Class::RU11W => "Recurve 1-10 female",
-                Class::RU18M => "Recurve 15-17 male",
-                Class::RU18W => "Recurve 15-17 female",
-                Class::RU21M => "Recurve 18-20 male",
-                Class::RU21W => "Recurve 18-20 female",
-                Class::RUE49M => "Recurve 50-65 male",
-                Class::RUE49W => "Recurve 50-65 female",
-                Class::RUE65M => "Recurve 66+ male",
-                Class::RUE65W => "Recurve 66+ female",
-                Class::BUE20M => "Barebow Men",
-                Class::BUE20W => "Barebow Women",
-                Class::BU15 => "Barebow 1-14 male/female",
-                Class::BU21 => "Barebow 15-20 male/female",
-                Class::CUE20M => "Compound Men",
-                Class::CUE20W => "Compound Women",
-                Class::CU15 => "Compound 1-14 male/female",
-                Class::CU21 => "Compound 15-20 male/female",
-                Class::CUE49M => "Compound 50-65 male",
-                Class::CUE49W => "Compound 50-65 female",
-                Class::CUE65M => "Compound 66+ male",
-                Class::CUE65W => "Compound 66+ female",
-            },
-            Locale::De => match self {
-                Class::RUE20M => "Recurve Herren",
-                Class::RUE20W => "Recurve Damen",
-                Class::RU15M => "Recurve Schüler A m",
-                Class::RU15W => "Recurve Schüler A w",
-                Class::RU13M => "Recurve Schüler B m",
-                Class::RU13W => "Recurve Schüler B w",
-                Class::RU11M => "Recurve Schüler C m",
-                Class::RU11W => "Recurve Schüler C w",
-                Class::RU18M => "Recurve Jugend m",
-                Class::RU18W => "Recurve Jugend w",
-                Class::RU21M => "Recurve Junioren m",
-                Class::RU21W => "Recurve Junioren w",
-                Class::RUE49M => "Recurve Master m",
-                Class::RUE49W => "Recurve Master w",
-                Class::RUE65M => "Recurve Senioren m",
-                Class::RUE65W => "Recurve Senioren w",
-                Class::BUE20M => "Blank Herren",
-                Class::BUE20W => "Blank Damen",
-                Class::BU15 => "Blank Schüler m/w",
-                Class::BU21 => "Blank Jugend/Junioren m/w",
-                Class::CUE20M => "Compound Herren",
-                Class::CUE20W => "Compound Damen",
-                Class::CU15 => "Compound Schüler m/w",
-                Class::CU21 => "Compound Jugend/Junioren m/w",
-                Class::CUE49M => "Compound Master m",
-                Class::CUE49W => "Compound Master w",
-                Class::CUE65M => "Compound Senioren m",
-                Class::CUE65W => "Compound Senioren w",
-            },
+            Locale::En => &self.en,
+            Locale::De => &self.de,
         }
     }
-    pub fn comment(&self) -> &'static str {
-        ""
-    }
-    pub fn all_classes() -> impl Iterator<Item = Self> {
-        Self::iter()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ClassDefinition {
+    pub code: String,
+    pub bow_type: BowType,
+    /// `None` for classes open to all genders
+    #[serde(default)]
+    pub gender: Option<Gender>,
+    /// Lowest age reached within the season
+    pub min_age: u32,
+    /// Highest age reached within the season
+    pub max_age: u32,
+    /// Price of starter in class in euro cent
+    pub price: u32,
+    /// Classes archers of this class may register for instead
+    #[serde(default)]
+    pub upgrades: Vec<String>,
+    pub target_faces: TargetFaces,
+    pub name: LocalizedName,
+    /// Whether archers of the class may sign up for finals
+    #[serde(default = "default_finals")]
+    pub finals: bool,
+}
+
+fn default_finals() -> bool {
+    true
+}
+
+impl ClassDefinition {
+    pub fn class(&self) -> Class {
+        Class::new(self.code.clone())
     }
-    pub fn recurve_classes() -> &'static [Self] {
-        &[
-            Self::RUE20M,
-            Self::RUE20W,
-            Self::RU15M,
-            Self::RU15W,
-            Self::RU13M,
-            Self::RU13W,
-            Self::RU11M,
-            Self::RU11W,
-            Self::RU18M,
-            Self::RU18W,
-            Self::RU21M,
-            Self::RU21W,
-            Self::RUE49M,
-            Self::RUE49W,
-            Self::RUE65M,
-            Self::RUE65W,
-        ]
+    pub fn name(&self, locale: Locale) -> &str {
+        self.name.get(locale)
     }
-    pub fn barebow_classes() -> &'static [Self] {
-        &[Self::BUE20M, Self::BUE20W, Self::BU15, Self::BU21]
+    /// Whether an archer born at `dob` reaches an age of the class within the season starting at `season_start`
+    pub fn in_range(&self, dob: NaiveDate, season_start: NaiveDate) -> bool {
+        let date_range = (season_start - Months::new(self.max_age * 12))
+            ..(season_start - Months::new((self.min_age - 1) * 12));
+        date_range.contains(&dob)
     }
-    pub fn compound_classes() -> &'static [Self] {
-        &[
-            Self::CUE20M,
-            Self::CUE20W,
-            Self::CU15,
-            Self::CU21,
-            Self::CUE49M,
-            Self::CUE49W,
-            Self::CUE65M,
-            Self::CUE65W,
-        ]
+}
+
+/// Class definitions of an association, loaded from a rule file
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Rules {
+    pub classes: Vec<ClassDefinition>,
+    #[serde(default)]
+    pub team_classes: Vec<TeamClassDefinition>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::from_toml(DEFAULT_RULES).expect("Default rules are valid")
     }
-    pub fn in_range(&self, dob: NaiveDate) -> bool {
-        let year_range = match self {
-            Class::RUE20M => (21, 49),
-            Class::RUE20W => (21, 49),
-            Class::RU15M => (13, 14),
-            Class::RU15W => (13, 14),
-            Class::RU13M => (11, 12),
-            Class::RU13W => (11, 12),
-            Class::RU11M => (1, 10),
-            Class::RU11W => (1, 10),
-            Class::RU18M => (15, 17),
-            Class::RU18W => (15, 17),
-            Class::RU21M => (18, 20),
-            Class::RU21W => (18, 20),
-            Class::RUE49M => (50, 65),
-            Class::RUE49W => (50, 65),
-            Class::RUE65M => (66, 120),
-            Class::RUE65W => (66, 120),
-
-            Class::CUE20M => (21, 49),
-            Class::CUE20W => (21, 49),
-            Class::CU15 => (1, 14),
-            Class::CU21 => (15, 20),
-            Class::CUE49M => (50, 65),
-            Class::CUE49W => (50, 65),
-            Class::CUE65M => (66, 120),
-            Class::CUE65W => (66, 120),
-
-            Class::BUE20M => (21, 120),
-            Class::BUE20W => (21, 120),
-            Class::BU15 => (1, 14),
-            Class::BU21 => (15, 20),
-        };
+}
 
-        let date_range = (*SEASON_START - Months::new(year_range.1 * 12))
-            ..(*SEASON_START - Months::new((year_range.0 - 1) * 12));
-        date_range.contains(&dob)
+impl Rules {
+    pub fn from_toml(s: &str) -> Result<Self, RulesError> {
+        let rules: Self = toml::from_str(s).map_err(|e| RulesError(e.to_string()))?;
+        rules.validate()?;
+        Ok(rules)
     }
 
-    // Price of starter in class in euro cent
-    pub fn price(&self) -> u32 {
-        use Class::*;
-        match self {
-            RU15M | RU15W | RU13M | RU13W | RU11M | RU11W | RU18M | RU18W | BU15 | BU21 | CU15
-            | CU21 => 1200,
-            _ => 1800,
+    fn validate(&self) -> Result<(), RulesError> {
+        for (index, cls) in self.classes.iter().enumerate() {
+            if self.classes[..index].iter().any(|c| c.code == cls.code) {
+                return Err(RulesError(format!("Class {} defined twice", cls.code)));
+            }
+            if cls.min_age == 0 || cls.min_age > cls.max_age {
+                return Err(RulesError(format!("Invalid age range of {}", cls.code)));
+            }
+            if cls.target_faces.is_empty() {
+                return Err(RulesError(format!("No target face for {}", cls.code)));
+            }
+            if let Some(upgrade) = cls
+                .upgrades
+                .iter()
+                .find(|u| !self.classes.iter().any(|c| &c.code == *u))
+            {
+                return Err(RulesError(format!(
+                    "Unknown upgrade {} of {}",
+                    upgrade, cls.code
+                )));
+            }
+        }
+        for (index, team) in self.team_classes.iter().enumerate() {
+            if self.team_classes[..index]
+                .iter()
+                .any(|t| t.code == team.code)
+                || self.classes.iter().any(|c| c.code == team.code)
+            {
+                return Err(RulesError(format!("Class {} defined twice", team.code)));
+            }
+            if let Some(member) = team
+                .member_classes
+                .iter()
+                .find(|m| !self.classes_of(team.bow_type).any(|c| &c.code == *m))
+            {
+                return Err(RulesError(format!(
+                    "Unknown member class {} of {}",
+                    member, team.code
+                )));
+            }
         }
+        Ok(())
     }
 
-    pub fn allowed_classes(bow_type: BowType, dob: NaiveDate) -> Vec<(Class, ClassUpgradeStatus)> {
-        let default_classes = match bow_type {
-            BowType::Recurve => Class::recurve_classes(),
-            BowType::Compound => Class::compound_classes(),
-            BowType::Barebow => Class::barebow_classes(),
-        }
-        .iter()
-        .filter(|cls| cls.in_range(dob));
+    pub fn class(&self, cls: &Class) -> Option<&ClassDefinition> {
+        self.classes.iter().find(|c| c.code == cls.code())
+    }
 
-        let upgrade_classes = default_classes
-            .clone()
-            .map(|dc| dc.other_allowed_classes().into_iter())
-            .flatten()
-            .unique();
-        default_classes
-            .map(|&c| (c, ClassUpgradeStatus::InDefaultAgeRange))
-            .chain(upgrade_classes.map(|&c| (c, ClassUpgradeStatus::Upgrade)))
-            .collect()
+    pub fn class_from_code(&self, code: &str) -> Result<&ClassDefinition, UnknownClassError> {
+        self.classes
+            .iter()
+            .find(|c| c.code == code)
+            .ok_or(UnknownClassError { class: code.into() })
     }
 
-    fn other_allowed_classes(&self) -> &'static [Self] {
-        use Class::*;
-        match self {
-            // Junioren + Master => Herren/Damen
-            RU21M | RUE49M => &[RUE20M],
-            RU21W | RUE49W => &[RUE20W],
-            RUE65M => &[RUE49M, RUE20M],
-            RUE65W => &[RUE49W, RUE20W],
+    pub fn classes_of(&self, bow_type: BowType) -> impl Iterator<Item = &ClassDefinition> + Clone {
+        self.classes.iter().filter(move |c| c.bow_type == bow_type)
+    }
 
-            BU21 => &[BUE20M, BUE20W],
+    /// Target faces of the class at the tournament, none if the class isn't offered
+    pub fn target_faces(&self, cls: &Class, tournament: &Tournament) -> &[TargetFace] {
+        self.class(cls)
+            .filter(|c| tournament.offers(c))
+            .map_or(&[], |c| c.target_faces.get(tournament.kind))
+    }
 
-            CU21 => &[CUE20M, CUE20W],
-            CUE49M => &[CUE20M],
-            CUE49W => &[CUE20W],
-            CUE65M => &[CUE49M, CUE20M],
-            CUE65W => &[CUE49W, CUE20W],
+    pub fn allowed_classes(
+        &self,
+        bow_type: BowType,
+        dob: NaiveDate,
+        tournament: &Tournament,
+    ) -> Vec<(Class, ClassUpgradeStatus)> {
+        let offered = |cls: &ClassDefinition| tournament.offers(cls);
+        let in_range = self
+            .classes_of(bow_type)
+            .filter(move |cls| cls.in_range(dob, tournament.season_start));
 
-            _ => &[],
-        }
+        // Upgrades stay possible if the default class isn't offered at the tournament
+        let upgrade_classes = in_range
+            .clone()
+            .flat_map(|dc| dc.upgrades.iter())
+            .unique()
+            .filter_map(|code| self.classes.iter().find(|c| &c.code == code))
+            .filter(|cls| offered(cls));
+        in_range
+            .filter(|cls| offered(cls))
+            .map(|c| (c.class(), ClassUpgradeStatus::InDefaultAgeRange))
+            .chain(upgrade_classes.map(|c| (c.class(), ClassUpgradeStatus::Upgrade)))
+            .collect()
+    }
+
+    /// Every class of the bow type offered at the tournament, with the reason why it is available or not
+    pub fn class_availability(
+        &self,
+        bow_type: BowType,
+        dob: NaiveDate,
+        tournament: &Tournament,
+    ) -> Vec<ClassStatus> {
+        let age = season_age(dob, tournament.season_start);
+        let in_range: Vec<_> = self
+            .classes_of(bow_type)
+            .filter(|cls| cls.in_range(dob, tournament.season_start))
+            .collect();
+        self.classes_of(bow_type)
+            .filter(|cls| tournament.offers(cls))
+            .map(|cls| ClassStatus {
+                class: cls.class(),
+                age,
+                availability: if in_range.contains(&cls) {
+                    ClassAvailability::InDefaultAgeRange
+                } else if in_range.iter().any(|c| c.upgrades.contains(&cls.code)) {
+                    ClassAvailability::Upgrade
+                } else if age < cls.min_age as i32 {
+                    ClassAvailability::TooYoung {
+                        min_age: cls.min_age,
+                    }
+                } else {
+                    ClassAvailability::TooOld {
+                        max_age: cls.max_age,
+                    }
+                },
+            })
+            .collect()
+    }
+
+    /// Allowed classes of all bow types
+    pub fn all_allowed_classes(&self, dob: NaiveDate, tournament: &Tournament) -> Vec<Class> {
+        BowType::iter()
+            .flat_map(|bow_type| self.allowed_classes(bow_type, dob, tournament))
+            .map(|(cls, _)| cls)
+            .collect()
     }
 }
 
@@ -258,16 +254,39 @@ pub enum ClassUpgradeStatus {
     Upgrade,
 }
 
-impl FromStr for Class {
-    type Err = UnknownClassError;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClassAvailability {
+    InDefaultAgeRange,
+    /// Allowed as upgrade from a class in the default age range
+    Upgrade,
+    TooYoung {
+        min_age: u32,
+    },
+    TooOld {
+        max_age: u32,
+    },
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::iter()
-            .find(|c| format!("{c:?}") == s)
-            .ok_or(UnknownClassError { class: s.into() })
+impl ClassAvailability {
+    pub fn is_available(&self) -> bool {
+        matches!(self, Self::InDefaultAgeRange | Self::Upgrade)
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassStatus {
+    pub class: Class,
+    pub availability: ClassAvailability,
+    /// Age reached within the season, which decides the availability
+    pub age: i32,
+}
+
+/// Age an archer born at `dob` reaches within the season starting at `season_start`
+pub fn season_age(dob: NaiveDate, season_start: NaiveDate) -> i32 {
+    let season_end = season_start + Months::new(12) - chrono::Days::new(1);
+    season_end.years_since(dob).map_or(-1, |years| years as i32)
+}
+
 #[derive(Debug)]
 pub struct UnknownClassError {
     pub class: String,
@@ -280,80 +299,225 @@ impl Display for UnknownClassError {
     }
 }
 
+#[derive(Debug)]
+pub struct RulesError(pub String);
+
+impl std::error::Error for RulesError {}
+impl Display for RulesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid rules: {}", self.0)
+    }
+}
+
 #[test]
 fn test_in_range() {
     use chrono::Datelike;
-    let now_ = SEASON_START.year() - 1;
-    let died = SEASON_START.year() - 120;
+    // Ages of the oldest and the youngest archers within the season
     let test_cases = std::collections::HashMap::from([
-        (Class::RUE20M, (1976, 2004)),
-        (Class::RUE20W, (1976, 2004)),
-        (Class::RU15M, (2011, 2012)),
-        (Class::RU15W, (2011, 2012)),
-        (Class::RU13M, (2013, 2014)),
-        (Class::RU13W, (2013, 2014)),
-        (Class::RU11M, (2015, now_)),
-        (Class::RU11W, (2015, now_)),
-        (Class::RU18M, (2008, 2010)),
-        (Class::RU18W, (2008, 2010)),
-        (Class::RU21M, (2005, 2007)),
-        (Class::RU21W, (2005, 2007)),
-        (Class::RUE49M, (1960, 1975)),
-        (Class::RUE49W, (1960, 1975)),
-        (Class::RUE65M, (died, 1959)),
-        (Class::RUE65W, (died, 1959)),
+        ("RUE20M", (49, 21)),
+        ("RUE20W", (49, 21)),
+        ("RU15M", (14, 13)),
+        ("RU15W", (14, 13)),
+        ("RU13M", (12, 11)),
+        ("RU13W", (12, 11)),
+        ("RU11M", (10, 1)),
+        ("RU11W", (10, 1)),
+        ("RU18M", (17, 15)),
+        ("RU18W", (17, 15)),
+        ("RU21M", (20, 18)),
+        ("RU21W", (20, 18)),
+        ("RUE49M", (65, 50)),
+        ("RUE49W", (65, 50)),
+        ("RUE65M", (120, 66)),
+        ("RUE65W", (120, 66)),
         //
-        (Class::BUE20M, (died, 2004)),
-        (Class::BUE20W, (died, 2004)),
-        (Class::BU15, (2011, now_)),
-        (Class::BU21, (2005, 2010)),
+        ("BUE20M", (120, 21)),
+        ("BUE20W", (120, 21)),
+        ("BU15", (14, 1)),
+        ("BU21", (20, 15)),
         //
-        (Class::CUE20M, (1976, 2004)),
-        (Class::CUE20W, (1976, 2004)),
-        (Class::CU15, (2011, now_)),
-        (Class::CU21, (2005, 2010)),
-        (Class::CUE49M, (1960, 1975)),
-        (Class::CUE49W, (1960, 1975)),
-        (Class::CUE65M, (died, 1959)),
-        (Class::CUE65W, (died, 1959)),
+        ("CUE20M", (49, 21)),
+        ("CUE20W", (49, 21)),
+        ("CU15", (14, 1)),
+        ("CU21", (20, 15)),
+        ("CUE49M", (65, 50)),
+        ("CUE49W", (65, 50)),
+        ("CUE65M", (120, 66)),
+        ("CUE65W", (120, 66)),
+        //
+        ("LUE20M", (120, 21)),
+        ("LUE20W", (120, 21)),
+        ("LU21", (20, 1)),
+        ("TUE20M", (120, 21)),
+        ("TUE20W", (120, 21)),
+        ("TU21", (20, 1)),
+        ("IUE20M", (120, 21)),
+        ("IUE20W", (120, 21)),
+        ("IU21", (20, 1)),
     ]);
+    let seasons = [
+        NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2019, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2030, 4, 15).unwrap(),
+    ];
+
+    let rules = Rules::default();
+    assert_eq!(rules.classes.len(), test_cases.len());
+    for season_start in seasons {
+        let born = |age: u32| {
+            season_start
+                .with_year(season_start.year() - age as i32)
+                .unwrap()
+        };
+        for cls in &rules.classes {
+            let (oldest, youngest) = test_cases[cls.code.as_str()];
+            let lower_in = born(oldest);
+            let lower_out = lower_in.pred_opt().unwrap();
+            let upper_out = born(youngest - 1);
+            let upper_in = upper_out.pred_opt().unwrap();
 
-    for cls in Class::iter() {
-        let range = test_cases[&cls];
-        let lower_out = NaiveDate::from_ymd_opt(range.0 - 1, 12, 31).unwrap();
-        let lower_in = NaiveDate::from_ymd_opt(range.0, 1, 1).unwrap();
-        let upper_in = NaiveDate::from_ymd_opt(range.1, 12, 31).unwrap();
-        let upper_out = NaiveDate::from_ymd_opt(range.1 + 1, 1, 1).unwrap();
-
-        println!("{}", lower_out);
-        println!("{}", lower_in);
-        println!("{}", upper_in);
-        println!("{}", upper_out);
-        println!();
-
-        assert!(
-            !cls.in_range(lower_out),
-            "Lower out bound not respected for {}: {}",
-            cls,
-            lower_out
-        );
-        assert!(
-            cls.in_range(lower_in),
-            "Lower in bound not respected for {}: {}",
-            cls,
-            lower_in
-        );
-        assert!(
-            cls.in_range(upper_in),
-            "Upper in bound not respected for {}: {}",
-            cls,
-            upper_in
-        );
-        assert!(
-            !cls.in_range(upper_out),
-            "Upper out bound not respected for {}: {}",
-            cls,
-            upper_out
-        );
+            assert!(
+                !cls.in_range(lower_out, season_start),
+                "Lower out bound not respected for {} in season {}: {}",
+                cls.code,
+                season_start,
+                lower_out
+            );
+            assert!(
+                cls.in_range(lower_in, season_start),
+                "Lower in bound not respected for {} in season {}: {}",
+                cls.code,
+                season_start,
+                lower_in
+            );
+            assert!(
+                cls.in_range(upper_in, season_start),
+                "Upper in bound not respected for {} in season {}: {}",
+                cls.code,
+                season_start,
+                upper_in
+            );
+            assert!(
+                !cls.in_range(upper_out, season_start),
+                "Upper out bound not respected for {} in season {}: {}",
+                cls.code,
+                season_start,
+                upper_out
+            );
+        }
     }
 }
+
+#[test]
+fn test_allowed_classes_follow_season() {
+    use crate::tournament::TournamentKind;
+    let rules = Rules::default();
+    let dob = NaiveDate::from_ymd_opt(2004, 6, 1).unwrap();
+    let classes = |season_start: NaiveDate| {
+        let tournament = Tournament {
+            season_start,
+            kind: TournamentKind::Indoor,
+            ..Default::default()
+        };
+        rules
+            .allowed_classes(BowType::Recurve, dob, &tournament)
+            .into_iter()
+            .map(|(cls, _)| cls.code().to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        classes(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()),
+        ["RU21M", "RU21W", "RUE20M", "RUE20W"]
+    );
+    assert_eq!(
+        classes(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()),
+        ["RUE20M", "RUE20W"]
+    );
+}
+
+#[test]
+fn test_target_faces_per_tournament_kind() {
+    use crate::tournament::TournamentKind;
+    let rules = DEFAULT_RULES.replace(r#", outdoor = ["M60cm122"], field = ["FieldBluePeg"]"#, "");
+    let rules = Rules::from_toml(&rules).unwrap();
+    let tournament = |kind| Tournament {
+        season_start: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+        kind,
+        ..Default::default()
+    };
+    let cls = Class::new("RUE65M");
+    assert_eq!(
+        rules.target_faces(&cls, &tournament(TournamentKind::Indoor)),
+        [TargetFace::M18Spot, TargetFace::M18cm40]
+    );
+    assert!(rules
+        .target_faces(&cls, &tournament(TournamentKind::Outdoor))
+        .is_empty());
+
+    // Senior without outdoor faces may only upgrade to classes offered outdoors
+    let dob = NaiveDate::from_ymd_opt(1950, 1, 1).unwrap();
+    let outdoor_classes: Vec<_> = rules
+        .allowed_classes(BowType::Recurve, dob, &tournament(TournamentKind::Outdoor))
+        .into_iter()
+        .map(|(cls, _)| cls.code().to_string())
+        .collect();
+    assert_eq!(outdoor_classes, ["RUE49M", "RUE20M", "RUE49W", "RUE20W"]);
+}
+
+#[test]
+fn test_class_availability() {
+    use crate::tournament::TournamentKind;
+    let rules = Rules::default();
+    let tournament = Tournament {
+        season_start: NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+        kind: TournamentKind::Indoor,
+        ..Default::default()
+    };
+    // Turns 14 in the season from October 2024 to September 2025
+    let dob = NaiveDate::from_ymd_opt(2011, 5, 1).unwrap();
+    assert_eq!(season_age(dob, tournament.season_start), 14);
+
+    let availability = rules.class_availability(BowType::Barebow, dob, &tournament);
+    let status = |code: &str| {
+        availability
+            .iter()
+            .find(|status| status.class.code() == code)
+            .unwrap()
+            .availability
+    };
+    assert_eq!(availability.len(), 4);
+    assert!(availability.iter().all(|status| status.age == 14));
+    assert_eq!(status("BU15"), ClassAvailability::InDefaultAgeRange);
+    assert_eq!(status("BU21"), ClassAvailability::TooYoung { min_age: 15 });
+    assert_eq!(
+        status("BUE20M"),
+        ClassAvailability::TooYoung { min_age: 21 }
+    );
+
+    let dob = NaiveDate::from_ymd_opt(2005, 5, 1).unwrap();
+    let availability = rules.class_availability(BowType::Barebow, dob, &tournament);
+    assert_eq!(
+        availability
+            .iter()
+            .map(|status| status.availability)
+            .collect::<Vec<_>>(),
+        [
+            ClassAvailability::Upgrade,
+            ClassAvailability::Upgrade,
+            ClassAvailability::TooOld { max_age: 14 },
+            ClassAvailability::InDefaultAgeRange,
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_rules() {
+    let rules = DEFAULT_RULES.replace(r#"upgrades = ["RUE20M"]"#, r#"upgrades = ["XYZ"]"#);
+    assert!(Rules::from_toml(&rules).is_err());
+    let rules = format!(
+        "{DEFAULT_RULES}\n{}",
+        &DEFAULT_RULES[DEFAULT_RULES.find("[[classes]]").unwrap()..]
+    );
+    assert!(Rules::from_toml(&rules).is_err());
+}
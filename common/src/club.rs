@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::duplicate::normalize;
+
+/// Club of the regional association with its stable code, used as Ianseo country code
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Club {
+    pub code: String,
+    pub name: String,
+}
+
+impl Club {
+    /// Whether the name is just another spelling of this club's name
+    pub fn is_named(&self, name: &str) -> bool {
+        normalize(&self.name) == normalize(name)
+    }
+
+    /// Whether all words of the search query appear in code or name
+    pub fn matches(&self, query: &str) -> bool {
+        let text = self.search_text();
+        Self::search_words(query)
+            .iter()
+            .all(|word| text.contains(word.as_str()))
+    }
+
+    /// Normalized code and name, stored to search the clubs in the database
+    pub fn search_text(&self) -> String {
+        format!("{} {}", normalize(&self.code), normalize(&self.name))
+    }
+
+    /// Normalized words of a search query
+    pub fn search_words(query: &str) -> Vec<String> {
+        normalize(query)
+            .split(' ')
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[test]
+fn test_club_matches() {
+    let club = Club {
+        code: "BY-123".into(),
+        name: "Polizei SV München".into(),
+    };
+    assert!(club.is_named("polizei sv  muenchen"));
+    assert!(!club.is_named("PSV"));
+    assert!(club.matches("münchen polizei"));
+    assert!(club.matches("by 123"));
+    assert!(!club.matches("augsburg"));
+}
@@ -0,0 +1,102 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Names with a Jaro-Winkler similarity of at least this value are considered similar
+const SIMILAR_NAME_THRESHOLD: f64 = 0.92;
+
+/// Personal data used to recognize an archer registered more than once
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ArcherIdentity {
+    pub first_name: String,
+    pub last_name: String,
+    pub date_of_birth: NaiveDate,
+    pub club: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum DuplicateReason {
+    SameNameAndDateOfBirth,
+    SimilarNameInClub,
+}
+
+/// Returned by the backend if a submitted archer seems to be registered already.
+/// The registered archer isn't named, its data is only shown to admins.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DuplicateWarning {
+    /// Index of the archer in the submitted registration
+    pub archer: usize,
+    pub reason: DuplicateReason,
+}
+
+impl ArcherIdentity {
+    pub fn duplicate_reason(&self, other: &Self) -> Option<DuplicateReason> {
+        let name = normalize(&format!("{} {}", self.first_name, self.last_name));
+        let other_name = normalize(&format!("{} {}", other.first_name, other.last_name));
+        if name == other_name && self.date_of_birth == other.date_of_birth {
+            Some(DuplicateReason::SameNameAndDateOfBirth)
+        } else if !self.club.trim().is_empty()
+            && normalize(&self.club) == normalize(&other.club)
+            && strsim::jaro_winkler(&name, &other_name) >= SIMILAR_NAME_THRESHOLD
+        {
+            Some(DuplicateReason::SimilarNameInClub)
+        } else {
+            None
+        }
+    }
+
+    /// Why `self` is likely the same person as one of the `existing` archers, if so
+    pub fn find_duplicate<'a>(
+        &self,
+        existing: impl IntoIterator<Item = &'a ArcherIdentity>,
+    ) -> Option<DuplicateReason> {
+        existing
+            .into_iter()
+            .find_map(|other| self.duplicate_reason(other))
+    }
+}
+
+/// Lower case, umlauts transcribed and everything but letters, digits and single spaces removed
+pub(crate) fn normalize(s: &str) -> String {
+    let mut normalized = String::with_capacity(s.len());
+    for c in s.trim().to_lowercase().chars() {
+        match c {
+            'ä' => normalized.push_str("ae"),
+            'ö' => normalized.push_str("oe"),
+            'ü' => normalized.push_str("ue"),
+            'ß' => normalized.push_str("ss"),
+            c if c.is_alphanumeric() => normalized.push(c),
+            '-' | ' ' if !normalized.ends_with(' ') => normalized.push(' '),
+            _ => (),
+        }
+    }
+    normalized.trim_end().to_string()
+}
+
+#[test]
+fn test_duplicate_reason() {
+    let archer =
+        |first_name: &str, last_name: &str, dob: (i32, u32, u32), club: &str| ArcherIdentity {
+            first_name: first_name.into(),
+            last_name: last_name.into(),
+            date_of_birth: NaiveDate::from_ymd_opt(dob.0, dob.1, dob.2).unwrap(),
+            club: club.into(),
+        };
+    let max = archer("Max", "Müller", (2010, 5, 3), "PSV München");
+
+    assert_eq!(
+        max.duplicate_reason(&archer(" max ", "Mueller", (2010, 5, 3), "Other club")),
+        Some(DuplicateReason::SameNameAndDateOfBirth)
+    );
+    assert_eq!(
+        max.duplicate_reason(&archer("Max", "Muller", (2010, 3, 5), "psv münchen")),
+        Some(DuplicateReason::SimilarNameInClub)
+    );
+    assert_eq!(
+        max.duplicate_reason(&archer("Max", "Muller", (2010, 3, 5), "Other club")),
+        None
+    );
+    assert_eq!(
+        max.duplicate_reason(&archer("Moritz", "Müller", (2012, 1, 1), "PSV München")),
+        None
+    );
+}
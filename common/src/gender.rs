@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Hash)]
+pub enum Gender {
+    Male,
+    Female,
+}
+
+impl Gender {
+    /// Gender code used by Ianseo
+    pub fn ianseo_code(&self) -> i32 {
+        match self {
+            Gender::Male => 0,
+            Gender::Female => 1,
+        }
+    }
+    pub fn from_ianseo_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(Gender::Male),
+            1 => Some(Gender::Female),
+            _ => None,
+        }
+    }
+}
@@ -1,7 +1,12 @@
 pub mod archer;
 pub mod bow_type;
 pub mod class;
+pub mod club;
+pub mod duplicate;
+pub mod gender;
 pub mod line_data;
 pub mod locale;
 pub mod target_face;
+pub mod team;
+pub mod tournament;
 pub use rust_i18n;
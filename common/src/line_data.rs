@@ -7,6 +7,26 @@ pub struct CreateArchersPayload {
     pub comment: String,
     pub club: String,
     pub archers: Vec<crate::archer::Archer>,
+    /// Teams formed of the archers
+    #[serde(default)]
+    pub teams: Vec<crate::team::Team>,
+    #[serde(default)]
+    pub locale: crate::locale::Locale,
+    /// Register even if some archers seem to be registered already
+    #[serde(default)]
+    pub ignore_duplicates: bool,
+    /// Honeypot field. Hidden in the form, so only bots fill it.
+    #[serde(default)]
+    pub website: String,
+    /// Stamp the server issued when the form was opened, to measure the time taken to fill it
+    #[serde(default)]
+    pub form_stamp: String,
+}
+
+/// Asks for a link to fill in the archers registered with the mail address before
+#[derive(Debug, serde::Deserialize, serde::Serialize, PartialEq, Eq)]
+pub struct PrefillRequest {
+    pub mail: EmailAddress,
     #[serde(default)]
     pub locale: crate::locale::Locale,
 }
@@ -32,7 +52,11 @@ fn test_deserialize_create_archers_payload_missing_locale() {
             comment: "".into(),
             club: "PSV".into(),
             archers: vec![],
-            locale: crate::locale::Locale::De
+            teams: vec![],
+            locale: crate::locale::Locale::De,
+            ignore_duplicates: false,
+            website: "".into(),
+            form_stamp: "".into(),
         }
     )
 }
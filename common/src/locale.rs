@@ -8,6 +8,7 @@ pub enum Locale {
 }
 
 impl Locale {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "en" => Some(Self::En),
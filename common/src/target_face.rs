@@ -1,26 +1,27 @@
-use crate::class::Class;
+use crate::tournament::TournamentKind;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, PartialOrd)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, PartialOrd, strum::EnumString,
+)]
 pub enum TargetFace {
     M18cm80,
     M18cm60,
     M18cm40,
     M18Spot,
-}
-
-impl TargetFace {
-    pub fn for_cls(cls: Class) -> &'static [TargetFace] {
-        use Class::*;
-        use TargetFace::*;
-        match cls {
-            RU11M | RU11W | RU13M | RU13W => &[M18cm80],
-            RU15M | RU15W | CU15 | BU15 => &[M18cm60],
-            BUE20M | BUE20W | BU21 => &[M18cm40],
-            CUE20M | CUE20W | CUE49M | CUE49W | CUE65M | CUE65W | CU21 => &[M18Spot],
-            _ => &[M18Spot, M18cm40],
-        }
-    }
+    M70cm122,
+    M60cm122,
+    M50cm122,
+    M40cm122,
+    M30cm122,
+    M40cm80,
+    M30cm80,
+    /// Compound 6-ring face
+    M50cm80Spot,
+    FieldRedPeg,
+    FieldBluePeg,
+    FieldYellowPeg,
+    FieldWhitePeg,
 }
 
 impl std::fmt::Display for TargetFace {
@@ -33,7 +34,42 @@ impl std::fmt::Display for TargetFace {
                 TargetFace::M18cm60 => "18m / 60cm",
                 TargetFace::M18cm40 => "18m / 40cm",
                 TargetFace::M18Spot => "18m / Spot",
+                TargetFace::M70cm122 => "70m / 122cm",
+                TargetFace::M60cm122 => "60m / 122cm",
+                TargetFace::M50cm122 => "50m / 122cm",
+                TargetFace::M40cm122 => "40m / 122cm",
+                TargetFace::M30cm122 => "30m / 122cm",
+                TargetFace::M40cm80 => "40m / 80cm",
+                TargetFace::M30cm80 => "30m / 80cm",
+                TargetFace::M50cm80Spot => "50m / 80cm Spot",
+                TargetFace::FieldRedPeg => "Field / red peg",
+                TargetFace::FieldBluePeg => "Field / blue peg",
+                TargetFace::FieldYellowPeg => "Field / yellow peg",
+                TargetFace::FieldWhitePeg => "Field / white peg",
             }
         )
     }
 }
+
+/// Target faces a class may choose from, per kind of tournament.
+/// A class without faces for a kind isn't offered at such tournaments.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TargetFaces {
+    pub indoor: Vec<TargetFace>,
+    pub outdoor: Vec<TargetFace>,
+    pub field: Vec<TargetFace>,
+}
+
+impl TargetFaces {
+    pub fn get(&self, kind: TournamentKind) -> &[TargetFace] {
+        match kind {
+            TournamentKind::Indoor => &self.indoor,
+            TournamentKind::Outdoor => &self.outdoor,
+            TournamentKind::Field => &self.field,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.indoor.is_empty() && self.outdoor.is_empty() && self.field.is_empty()
+    }
+}
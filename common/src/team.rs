@@ -0,0 +1,245 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    archer::Archer,
+    bow_type::BowType,
+    class::{Class, LocalizedName, Rules},
+    locale::Locale,
+    tournament::Tournament,
+};
+
+/// Number of archers in a team
+pub const TEAM_SIZE: usize = 3;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TeamClassDefinition {
+    pub code: String,
+    pub bow_type: BowType,
+    /// Classes the members may belong to. All classes of the bow type if empty.
+    #[serde(default)]
+    pub member_classes: Vec<String>,
+    /// Price of a team in euro cent
+    pub price: u32,
+    pub name: LocalizedName,
+}
+
+impl TeamClassDefinition {
+    pub fn name(&self, locale: Locale) -> &str {
+        self.name.get(locale)
+    }
+    fn admits(&self, cls: &Class) -> bool {
+        self.member_classes.is_empty() || self.member_classes.iter().any(|m| m == cls.code())
+    }
+}
+
+/// Team formed by a registrator of their archers
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Team {
+    pub name: String,
+    /// Indices of the members in the archers of the registration
+    pub members: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TeamError {
+    MissingName,
+    WrongSize,
+    UnknownMember,
+    /// An archer is member of a team twice or of two teams
+    DuplicateMember,
+    DifferentBowTypes,
+    /// No team class offered at the tournament admits all members
+    NoTeamClass,
+}
+
+impl std::error::Error for TeamError {}
+impl Display for TeamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TeamError::MissingName => write!(f, "Team has no name"),
+            TeamError::WrongSize => write!(f, "Team needs {} members", TEAM_SIZE),
+            TeamError::UnknownMember => write!(f, "Team member is not registered"),
+            TeamError::DuplicateMember => write!(f, "Archer is in more than one team"),
+            TeamError::DifferentBowTypes => write!(f, "Team members shoot different bow types"),
+            TeamError::NoTeamClass => write!(f, "No team class for the members"),
+        }
+    }
+}
+
+impl Rules {
+    pub fn team_class(&self, code: &str) -> Option<&TeamClassDefinition> {
+        self.team_classes.iter().find(|t| t.code == code)
+    }
+
+    /// Team class of a team with members of the given classes
+    pub fn team_class_of(
+        &self,
+        members: &[&Class],
+        tournament: &Tournament,
+    ) -> Result<&TeamClassDefinition, TeamError> {
+        let bow_types: Vec<_> = members
+            .iter()
+            .map(|cls| self.class(cls).map(|c| c.bow_type))
+            .collect::<Option<_>>()
+            .ok_or(TeamError::NoTeamClass)?;
+        let Some(&bow_type) = bow_types.first() else {
+            return Err(TeamError::WrongSize);
+        };
+        if bow_types.iter().any(|&bt| bt != bow_type) {
+            return Err(TeamError::DifferentBowTypes);
+        }
+        self.team_classes
+            .iter()
+            .filter(|team| team.bow_type == bow_type && tournament.offers_team(team))
+            .find(|team| members.iter().all(|cls| team.admits(cls)))
+            .ok_or(TeamError::NoTeamClass)
+    }
+
+    /// Checks the teams of a registration and returns their team classes.
+    /// The error names the index of the invalid team.
+    pub fn validate_teams(
+        &self,
+        teams: &[Team],
+        archers: &[Archer],
+        tournament: &Tournament,
+    ) -> Result<Vec<&TeamClassDefinition>, (usize, TeamError)> {
+        let mut assigned = Vec::new();
+        teams
+            .iter()
+            .enumerate()
+            .map(|(index, team)| {
+                if team.name.trim().is_empty() {
+                    return Err((index, TeamError::MissingName));
+                }
+                if team.members.len() != TEAM_SIZE {
+                    return Err((index, TeamError::WrongSize));
+                }
+                let mut classes = Vec::new();
+                for &member in &team.members {
+                    let archer = archers
+                        .get(member)
+                        .ok_or((index, TeamError::UnknownMember))?;
+                    if assigned.contains(&member) {
+                        return Err((index, TeamError::DuplicateMember));
+                    }
+                    assigned.push(member);
+                    classes.push(archer.class());
+                }
+                self.team_class_of(&classes, tournament)
+                    .map_err(|e| (index, e))
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_team_class() {
+    let rules = Rules::default();
+    let tournament = Tournament::default();
+    let team_class = |codes: &[&str]| {
+        let classes: Vec<_> = codes.iter().map(|c| Class::new(*c)).collect();
+        rules
+            .team_class_of(&classes.iter().collect::<Vec<_>>(), &tournament)
+            .map(|t| t.code.as_str())
+    };
+    assert_eq!(team_class(&["RU15M", "RU13W", "RU18M"]), Ok("RJT"));
+    assert_eq!(team_class(&["RU15M", "RUE20W", "RU18M"]), Ok("RT"));
+    assert_eq!(team_class(&["CU21", "CU15", "CU15"]), Ok("CJT"));
+    assert_eq!(
+        team_class(&["RUE20M", "CUE20M", "RUE20M"]),
+        Err(TeamError::DifferentBowTypes)
+    );
+    let only_adults = Tournament {
+        classes: vec!["RUE20M".into(), "RT".into()],
+        ..Default::default()
+    };
+    assert_eq!(
+        rules
+            .team_class_of(&[&Class::new("RUE20M")], &only_adults)
+            .map(|t| t.code.as_str()),
+        Ok("RT")
+    );
+    assert_eq!(
+        rules
+            .team_class_of(&[&Class::new("RU15M")], &only_adults)
+            .map(|t| t.code.as_str()),
+        Ok("RT")
+    );
+    let no_teams = Tournament {
+        classes: vec!["RUE20M".into()],
+        ..Default::default()
+    };
+    assert_eq!(
+        rules
+            .team_class_of(&[&Class::new("RUE20M")], &no_teams)
+            .map(|t| t.code.as_str()),
+        Err(TeamError::NoTeamClass)
+    );
+}
+
+#[test]
+fn test_validate_teams() {
+    use chrono::NaiveDate;
+    use std::str::FromStr;
+    let rules = Rules::default();
+    let tournament = Tournament {
+        season_start: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+        ..Default::default()
+    };
+    let archer = |cls: &str| {
+        Archer::new(
+            "Foo".into(),
+            "Bar".into(),
+            email_address::EmailAddress::from_str("foo@bar.com").unwrap(),
+            NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(),
+            Class::new(cls),
+            crate::target_face::TargetFace::M18Spot,
+            String::new(),
+            "PSV".into(),
+            0,
+            None,
+            crate::archer::Finals::default(),
+            &rules,
+            &tournament,
+        )
+        .unwrap()
+    };
+    let archers = [
+        archer("RUE20M"),
+        archer("RUE20W"),
+        archer("RUE20M"),
+        archer("CUE20M"),
+    ];
+    let team = |members: Vec<usize>| Team {
+        name: "PSV 1".into(),
+        members,
+    };
+    assert_eq!(
+        rules
+            .validate_teams(&[team(vec![0, 1, 2])], &archers, &tournament)
+            .map(|classes| classes.len()),
+        Ok(1)
+    );
+    assert_eq!(
+        rules.validate_teams(&[team(vec![0, 1])], &archers, &tournament),
+        Err((0, TeamError::WrongSize))
+    );
+    assert_eq!(
+        rules.validate_teams(&[team(vec![0, 1, 4])], &archers, &tournament),
+        Err((0, TeamError::UnknownMember))
+    );
+    assert_eq!(
+        rules.validate_teams(
+            &[team(vec![0, 1, 2]), team(vec![2, 1, 0])],
+            &archers,
+            &tournament
+        ),
+        Err((1, TeamError::DuplicateMember))
+    );
+    assert_eq!(
+        rules.validate_teams(&[team(vec![0, 1, 3])], &archers, &tournament),
+        Err((0, TeamError::DifferentBowTypes))
+    );
+}
@@ -0,0 +1,199 @@
+use crate::class::{ClassDefinition, LocalizedName};
+use crate::team::TeamClassDefinition;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Settings of the tournament archers register for
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Tournament {
+    /// Identifies the tournament in urls, e.g. `indoor25`
+    pub slug: String,
+    pub name: String,
+    pub date: NaiveDate,
+    #[serde(default)]
+    pub venue: String,
+    /// Page with further information, linked in the registration mails
+    #[serde(default)]
+    pub info_url: String,
+    /// Reference date of the age classes. Ages are counted within the twelve months starting at this date.
+    pub season_start: NaiveDate,
+    #[serde(default)]
+    pub kind: TournamentKind,
+    /// Sessions archers choose from, referenced by their index
+    #[serde(default = "default_sessions")]
+    pub sessions: Vec<LocalizedName>,
+    /// Codes of the classes and team classes offered. All classes of the rules are offered if empty.
+    #[serde(default)]
+    pub classes: Vec<String>,
+    /// Prices in euro cent by class code, overriding the prices of the rules
+    #[serde(default)]
+    pub prices: BTreeMap<String, u32>,
+    #[serde(default)]
+    pub registration_opens: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub registration_closes: Option<NaiveDateTime>,
+    /// Set once the tournament is archived. Archived tournaments never open again.
+    #[serde(default)]
+    pub archived_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TournamentKind {
+    #[default]
+    Indoor,
+    Outdoor,
+    Field,
+}
+
+impl Default for Tournament {
+    /// Placeholder until the tournament is loaded. The season is fixed, so classes computed
+    /// for it don't change over the years.
+    fn default() -> Self {
+        let season_start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        Self {
+            slug: String::new(),
+            name: String::new(),
+            date: season_start,
+            venue: String::new(),
+            info_url: String::new(),
+            season_start,
+            kind: TournamentKind::default(),
+            sessions: default_sessions(),
+            classes: Vec::new(),
+            prices: BTreeMap::new(),
+            registration_opens: None,
+            registration_closes: None,
+            archived_at: None,
+        }
+    }
+}
+
+fn default_sessions() -> Vec<LocalizedName> {
+    vec![
+        LocalizedName {
+            en: "Morning".into(),
+            de: "Vormittag".into(),
+        },
+        LocalizedName {
+            en: "Afternoon".into(),
+            de: "Nachmittag".into(),
+        },
+    ]
+}
+
+impl Tournament {
+    /// Whether archers can register for the class at this tournament
+    pub fn offers(&self, cls: &ClassDefinition) -> bool {
+        !cls.target_faces.get(self.kind).is_empty()
+            && (self.classes.is_empty() || self.classes.contains(&cls.code))
+    }
+
+    /// Price of a starter in the class in euro cent
+    pub fn price(&self, cls: &ClassDefinition) -> u32 {
+        self.prices.get(&cls.code).copied().unwrap_or(cls.price)
+    }
+
+    /// Whether teams of the team class can register at this tournament
+    pub fn offers_team(&self, team: &TeamClassDefinition) -> bool {
+        self.classes.is_empty() || self.classes.contains(&team.code)
+    }
+
+    /// Price of a team in the team class in euro cent
+    pub fn team_price(&self, team: &TeamClassDefinition) -> u32 {
+        self.prices.get(&team.code).copied().unwrap_or(team.price)
+    }
+
+    /// Whether archers can register for the session. The sessions are followed by a waiting
+    /// list for each of them.
+    pub fn has_session(&self, session: u8) -> bool {
+        usize::from(session) < 2 * self.sessions.len()
+    }
+
+    pub fn is_open(&self, now: NaiveDateTime) -> bool {
+        self.archived_at.is_none()
+            && self.registration_opens.is_none_or(|opens| opens <= now)
+            && self.registration_closes.is_none_or(|closes| now < closes)
+    }
+
+    /// Same tournament some years later. All dates are shifted and the year in the name is replaced.
+    pub fn next_edition(&self, slug: String, years: i32) -> Tournament {
+        let shift = |date: NaiveDate| {
+            let year = date.year() + years;
+            // 29th of February only exists in leap years
+            date.with_year(year)
+                .unwrap_or_else(|| NaiveDate::from_ymd_opt(year, 2, 28).unwrap())
+        };
+        let shift_time = |time: NaiveDateTime| shift(time.date()).and_time(time.time());
+        Tournament {
+            slug,
+            name: self.name.replace(
+                &self.date.year().to_string(),
+                &(self.date.year() + years).to_string(),
+            ),
+            date: shift(self.date),
+            season_start: shift(self.season_start),
+            registration_opens: self.registration_opens.map(shift_time),
+            registration_closes: self.registration_closes.map(shift_time),
+            archived_at: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[test]
+fn test_offered_classes_and_prices() {
+    use crate::class::{Class, Rules};
+    let rules = Rules::default();
+    let tournament = Tournament {
+        classes: vec!["RUE20M".into(), "RUE20W".into()],
+        prices: BTreeMap::from([("RUE20W".into(), 2000)]),
+        registration_closes: NaiveDate::from_ymd_opt(2025, 2, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0),
+        ..Default::default()
+    };
+    let cls = |code: &str| rules.class(&Class::new(code)).unwrap();
+    assert!(tournament.offers(cls("RUE20M")));
+    assert!(!tournament.offers(cls("CUE20M")));
+    assert_eq!(tournament.price(cls("RUE20M")), 1800);
+    assert_eq!(tournament.price(cls("RUE20W")), 2000);
+    assert!(tournament.is_open(
+        NaiveDate::from_ymd_opt(2025, 1, 31)
+            .unwrap()
+            .and_hms_opt(23, 59, 0)
+            .unwrap()
+    ));
+    assert!(!tournament.is_open(
+        NaiveDate::from_ymd_opt(2025, 2, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    ));
+}
+
+#[test]
+fn test_next_edition() {
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    let tournament = Tournament {
+        slug: "indoor24".into(),
+        name: "PSV Indoor 2024".into(),
+        date: date(2024, 2, 29),
+        season_start: date(2024, 1, 1),
+        registration_closes: date(2024, 2, 20).and_hms_opt(12, 0, 0),
+        archived_at: date(2024, 3, 1).and_hms_opt(0, 0, 0),
+        ..Default::default()
+    };
+    let next = tournament.next_edition("indoor25".into(), 1);
+    assert_eq!(next.slug, "indoor25");
+    assert_eq!(next.name, "PSV Indoor 2025");
+    assert_eq!(next.date, date(2025, 2, 28));
+    assert_eq!(next.season_start, date(2025, 1, 1));
+    assert_eq!(
+        next.registration_closes,
+        date(2025, 2, 20).and_hms_opt(12, 0, 0)
+    );
+    assert_eq!(next.archived_at, None);
+    assert_eq!(next.sessions, tournament.sessions);
+    assert!(!tournament.is_open(date(2024, 1, 1).and_hms_opt(0, 0, 0).unwrap()));
+}
@@ -42,6 +42,8 @@
           ] ++ pkgs.lib.lists.optional pkgs.stdenv.isDarwin [ pkgs.darwin.apple_sdk.frameworks.Security ];
           buildInputs = [
             pkgs.sqlite.dev
+            # libpq for the `postgres` feature
+            pkgs.postgresql.lib
           ];
         };
 
@@ -2,16 +2,19 @@ use std::ops::BitXor;
 
 use chrono::NaiveDate;
 use common::{
+    archer::{Finals, PastArcher},
     bow_type::BowType,
-    class::{Class, ClassUpgradeStatus},
+    class::{Class, ClassAvailability},
+    gender::Gender,
     locale::Locale,
     target_face::TargetFace,
 };
 use rust_i18n::t;
 use seed::{prelude::*, *};
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 
-use crate::Msg;
+use crate::{tournament, with_rules, Msg};
 
 #[derive(Serialize, Deserialize)]
 pub enum DoB {
@@ -45,6 +48,11 @@ pub struct ArcherModel {
     pub bow_type: BowType,
     pub cls: Option<Class>,
     pub session: u8,
+    /// Only asked for classes open to all genders
+    #[serde(default)]
+    pub gender: Option<Gender>,
+    #[serde(default)]
+    pub finals: Finals,
 
     pub possible_target_faces: Vec<TargetFace>,
     pub selected_target_face: TargetFace,
@@ -52,10 +60,9 @@ pub struct ArcherModel {
 
 impl ArcherModel {
     pub fn update_target_face(&mut self) {
-        self.possible_target_faces = if let Some(cls) = self.cls {
-            TargetFace::for_cls(cls).to_owned()
-        } else {
-            Vec::new()
+        self.possible_target_faces = match &self.cls {
+            Some(cls) => with_rules(|rules| rules.target_faces(cls, &tournament()).to_owned()),
+            None => Vec::new(),
         };
         if !self
             .possible_target_faces
@@ -63,29 +70,31 @@ impl ArcherModel {
         {
             self.selected_target_face = *self
                 .possible_target_faces
-                .get(0)
+                .first()
                 .unwrap_or(&TargetFace::M18Spot);
         }
     }
     pub fn check_and_update_cls(&mut self, index: usize, orders: &mut impl Orders<Msg>) {
         let available_classes = match self.date_of_birth {
-            DoB::Vaild(dob) => Class::allowed_classes(self.bow_type, dob)
-                .into_iter()
-                .map(|(cls, _)| cls)
-                .collect::<Vec<_>>(),
+            DoB::Vaild(dob) => {
+                with_rules(|rules| rules.allowed_classes(self.bow_type, dob, &tournament()))
+                    .into_iter()
+                    .map(|(cls, _)| cls)
+                    .collect::<Vec<_>>()
+            }
             DoB::Invalid(_) => Vec::new(),
         };
 
-        let new_cls = match (self.cls, available_classes.get(0)) {
-            (Some(cls), Some(&new)) => {
-                if available_classes.contains(&cls) {
+        let new_cls = match (&self.cls, available_classes.first()) {
+            (Some(cls), Some(new)) => {
+                if available_classes.contains(cls) {
                     return;
                 } else {
-                    Some(new)
+                    Some(new.clone())
                 }
             }
             (_, None) => None,
-            (None, Some(&new)) => Some(new),
+            (None, Some(new)) => Some(new.clone()),
         };
 
         self.update_target_face();
@@ -97,22 +106,66 @@ impl ArcherModel {
     pub fn ready_for_submission(&self) -> bool {
         !self.first_name.is_empty().bitxor(self.last_name.is_empty())
             && self.cls.is_some()
+            && (!self.is_gender_neutral() || self.gender.is_some())
             && self.date_of_birth.is_valid()
     }
+
+    /// Whether the selected class may sign up for finals
+    pub fn may_shoot_finals(&self) -> bool {
+        self.cls
+            .as_ref()
+            .is_some_and(|cls| with_rules(|rules| rules.class(cls).is_some_and(|cls| cls.finals)))
+    }
+
+    /// Whether the selected class is open to all genders, so the gender has to be asked
+    pub fn is_gender_neutral(&self) -> bool {
+        self.cls.as_ref().is_some_and(|cls| {
+            with_rules(|rules| rules.class(cls).is_some_and(|cls| cls.gender.is_none()))
+        })
+    }
+}
+impl From<PastArcher> for ArcherModel {
+    /// Class and target face are chosen once the classes are checked for the tournament
+    fn from(past: PastArcher) -> Self {
+        Self {
+            first_name: past.first_name,
+            last_name: past.last_name,
+            date_of_birth: DoB::Vaild(past.date_of_birth),
+            bow_type: past.bow_type,
+            cls: None,
+            gender: past.gender,
+            ..Default::default()
+        }
+    }
 }
+
 impl Default for ArcherModel {
     fn default() -> Self {
         let date = NaiveDate::default();
-        let cls = Class::allowed_classes(BowType::Recurve, date)[0].0;
+        let tournament = tournament();
+        // The tournament may offer no recurve class for the date, the class is chosen later then
+        let (cls, target_faces) = with_rules(|rules| {
+            let cls = rules
+                .allowed_classes(BowType::Recurve, date, &tournament)
+                .first()
+                .map(|(cls, _)| cls.clone());
+            let target_faces = cls
+                .as_ref()
+                .map(|cls| rules.target_faces(cls, &tournament).to_owned())
+                .unwrap_or_default();
+            (cls, target_faces)
+        });
         Self {
             first_name: String::new(),
             last_name: String::new(),
             date_of_birth: DoB::Vaild(date),
             bow_type: BowType::Recurve,
-            cls: Some(cls),
+            cls,
             session: 0,
-            possible_target_faces: TargetFace::for_cls(cls).to_owned(),
-            selected_target_face: TargetFace::for_cls(cls)[0],
+            gender: None,
+            finals: Finals::default(),
+            selected_target_face: *target_faces.first().unwrap_or(&TargetFace::M18Spot),
+            possible_target_faces: target_faces,
         }
     }
 }
@@ -125,15 +178,43 @@ pub enum ArcherMsg {
     ClassChanged(Option<Class>),
     SessionChanged(u8),
     TargetFaceChanged(TargetFace),
+    GenderChanged(Gender),
+    FinalsChanged(Finals),
 }
 
-pub fn archer_view(model: &ArcherModel, index: usize) -> Node<Msg> {
+/// `in_team` tells whether the archer is member of a team, which the team final requires
+pub fn archer_view(model: &ArcherModel, index: usize, in_team: bool) -> Node<Msg> {
+    let locale = Locale::from_str(&rust_i18n::locale()).unwrap();
     let dob = &model.date_of_birth;
     let bow_type = model.bow_type;
-    let allowed_classes = match dob {
-        DoB::Vaild(dob) => Class::allowed_classes(bow_type, *dob),
+    let mut class_availability = match dob {
+        DoB::Vaild(dob) => {
+            with_rules(|rules| rules.class_availability(bow_type, *dob, &tournament()))
+        }
         DoB::Invalid(_) => Vec::new(),
     };
+    // Regular classes first, unavailable ones last
+    class_availability.sort_by_key(|status| match status.availability {
+        ClassAvailability::InDefaultAgeRange => 0,
+        ClassAvailability::Upgrade => 1,
+        _ => 2,
+    });
+    let allowed_classes: Vec<_> = class_availability
+        .iter()
+        .filter(|status| status.availability.is_available())
+        .map(|status| status.class.clone())
+        .collect();
+    // Only bow types with classes offered at the tournament
+    let kind = tournament().kind;
+    let bow_types: Vec<_> = with_rules(|rules| {
+        BowType::iter()
+            .filter(|&bt| {
+                rules
+                    .classes_of(bt)
+                    .any(|cls| !cls.target_faces.get(kind).is_empty())
+            })
+            .collect()
+    });
 
     p![
         C!("archer"),
@@ -185,35 +266,21 @@ pub fn archer_view(model: &ArcherModel, index: usize) -> Node<Msg> {
         li!(br!()),
         li!(t!("Session")),
         li!(
-            input!(
-                attrs!(At::Type => "radio", At::Name => format!("session{}", index), At::Id => format!("session1-{}", index)),
-                if model.session == 0 {
-                    Some(attrs!("checked" => AtValue::None))
-                } else {
-                    None
-                },
-                input_ev(Ev::Input, move |_| Msg::ArcherMsg(
-                    index,
-                ArcherMsg::SessionChanged(0),
-                )),
-            ),
-            label!(t!("Morning"), attrs!(At::For => format!("session1-{}", index))),
-            br!(),
-
-            input!(
-                attrs!(At::Type => "radio", At::Name => format!("session{}", index), At::Id => format!("session2-{}", index)),
-                if model.session == 1 {
-                    Some(attrs!("checked" => AtValue::None))
-                } else {
-                    None
-                },
-                input_ev(Ev::Input, move |_| Msg::ArcherMsg(
-                    index,
-                    ArcherMsg::SessionChanged(1),
-                )),
-            ),
-            label!(t!("Afternoon"), attrs!(At::For => format!("session2-{}", index))),
-            br!(),
+            tournament().sessions.iter().enumerate().map(|(session, name)| {
+                let session = session as u8;
+                vec![
+                    input!(
+                        attrs!(At::Type => "radio", At::Name => format!("session{}", index), At::Id => format!("session{}-{}", session + 1, index)),
+                        IF!(model.session == session => attrs!(At::Checked => AtValue::None)),
+                        input_ev(Ev::Input, move |_| Msg::ArcherMsg(
+                            index,
+                            ArcherMsg::SessionChanged(session),
+                        )),
+                    ),
+                    label!(name.get(locale), attrs!(At::For => format!("session{}-{}", session + 1, index))),
+                    br!(),
+                ]
+            }),
 
             // input!(
             //     attrs!(At::Type => "radio", At::Name => format!("session{}", index), At::Id => format!("session3-{}", index)),
@@ -246,65 +313,38 @@ pub fn archer_view(model: &ArcherModel, index: usize) -> Node<Msg> {
         ),
         li!(br!()),
         li!(t!("Bow type")),
-        li!(
+        li!(bow_types.into_iter().map(|bt| vec![
             input!(
-                attrs!(At::Type => "radio", At::Name => format!("bow_type{}", index), At::Id => format!("recurve{}",index)),
-                if model.bow_type.is_recurve() {
-                    Some(attrs!("checked" => AtValue::None))
-                } else {
-                    None
-                },
+                attrs!(At::Type => "radio", At::Name => format!("bow_type{}", index), At::Id => format!("{:?}{}", bt, index)),
+                IF!(model.bow_type == bt => attrs!(At::Checked => AtValue::None)),
                 input_ev(Ev::Input, move |_| Msg::ArcherMsg(
                     index,
-                    ArcherMsg::BowTypeChange(BowType::Recurve)
+                    ArcherMsg::BowTypeChange(bt)
                 ))
             ),
-            label!(t!("Recurve"), attrs!(At::For => format!("recurve{}", index))),
+            label!(bow_type_name(bt), attrs!(At::For => format!("{:?}{}", bt, index))),
             br!(),
-            input!(
-                attrs!(At::Type => "radio", At::Name => format!("bow_type{}", index), At::Id => format!("blank{}", index)),
-                if model.bow_type.is_barebow() {
-                    Some(attrs!("checked" => AtValue::None))
-                } else {
-                    None
-                },
-                input_ev(Ev::Input, move |_| Msg::ArcherMsg(
-                    index,
-                    ArcherMsg::BowTypeChange(BowType::Barebow)
-                ))
-            ),
-            label!(t!("Barebow"), attrs!(At::For => format!("blank{}", index))),
-            br!(),
-            input!(
-                attrs!(At::Type => "radio", At::Name => format!("bow_type{}", index), At::Id => format!("compound{}",index), ),
-                if model.bow_type.is_compound() {
-                    Some(attrs!("checked" => AtValue::None))
-                } else {
-                    None
-                },
-                input_ev(Ev::Input, move |_| Msg::ArcherMsg(
-                    index,
-                    ArcherMsg::BowTypeChange(BowType::Compound)
-                ))
-            ),
-            label!(t!("Compound"), attrs!(At::For => format!("compound{}", index)))
-        ),
+        ])),
         li!(br!()),
         li!(t!("Class")),
         li!(
             attrs!(At::Name => "cls"),
             select!(
                 attrs!(At::Name => "Class",At::AutoComplete => "off", At::Required => AtValue::None),
-                model.cls.map(|cls| attrs!(At::Value => cls.to_string())),
-                allowed_classes.clone().into_iter()
-                .map(|(cls, upgrade_status)| option!(
-                    format!("{}{}",cls.name(Locale::from_str(&rust_i18n::locale()).unwrap()), if upgrade_status == ClassUpgradeStatus::Upgrade  {t!("Upgrade from regular class")} else{"".into()}),
-                    attrs!(At::Value => cls.to_string()),
-                    IF!(Some(cls) == model.cls => attrs!(At::Selected => AtValue::None)),
-                    ev(Ev::Input, move |_| {
-                        Msg::ArcherMsg(index, ArcherMsg::ClassChanged(Some(cls)))
-                    })
-                ))
+                model.cls.as_ref().map(|cls| attrs!(At::Value => cls.to_string())),
+                class_availability.into_iter()
+                .map(|status| {
+                    let cls = status.class;
+                    option!(
+                        format!("{}{}", class_name(&cls), availability_note(status.availability, status.age)),
+                        attrs!(At::Value => cls.to_string()),
+                        IF!(Some(&cls) == model.cls.as_ref() => attrs!(At::Selected => AtValue::None)),
+                        IF!(!status.availability.is_available() => attrs!(At::Disabled => AtValue::None)),
+                        ev(Ev::Input, move |_| {
+                            Msg::ArcherMsg(index, ArcherMsg::ClassChanged(Some(cls)))
+                        })
+                    )
+                })
                 .collect::<Vec<_>>(),
                 input_ev(Ev::Input, move |cls_id| {
                     Msg::ArcherMsg(
@@ -312,7 +352,6 @@ pub fn archer_view(model: &ArcherModel, index: usize) -> Node<Msg> {
                         ArcherMsg::ClassChanged(Some(
                             allowed_classes
                                 .into_iter()
-                            .map(|(cls, _)| cls)
                                 .find(|cls| cls.to_string() == cls_id)
                                 .unwrap(),
                         )),
@@ -320,6 +359,26 @@ pub fn archer_view(model: &ArcherModel, index: usize) -> Node<Msg> {
                 })
             )
         ),
+        IF!(model.is_gender_neutral() => vec![
+            li!(br!()),
+            li!(t!("Gender")),
+            li!(
+                [(Gender::Male, "male", t!("Male")), (Gender::Female, "female", t!("Female"))].map(
+                    |(gender, id, label)| vec![
+                        input!(
+                            attrs!(At::Type => "radio", At::Name => format!("gender{}", index), At::Id => format!("{}{}", id, index)),
+                            IF!(model.gender == Some(gender) => attrs!(At::Checked => AtValue::None)),
+                            input_ev(Ev::Input, move |_| Msg::ArcherMsg(
+                                index,
+                                ArcherMsg::GenderChanged(gender)
+                            ))
+                        ),
+                        label!(label, attrs!(At::For => format!("{}{}", id, index))),
+                        br!(),
+                    ]
+                )
+            ),
+        ]),
         li!(br!()),
         li!(t!("Target")),
         li!(model.possible_target_faces.iter().map(|&tf| div![
@@ -333,9 +392,73 @@ pub fn archer_view(model: &ArcherModel, index: usize) -> Node<Msg> {
             ),
             label!(format!("{}", tf), attrs!(At::For => format!("{}-{}", tf, index)))
         ]),),
+        IF!(model.may_shoot_finals() => vec![
+            li!(br!()),
+            li!(t!("Finals")),
+            li!(
+                [
+                    ("individual", t!("Individual final"), model.finals.individual, true),
+                    ("team", t!("Team final"), model.finals.team, in_team),
+                    ("mixed_team", t!("Mixed team final"), model.finals.mixed_team, true),
+                ].map(|(id, label, checked, enabled)| {
+                    let finals = model.finals;
+                    div![
+                        input!(
+                            attrs!(At::Type => "checkbox", At::Id => format!("{}_final{}", id, index)),
+                            IF!(checked => attrs!(At::Checked => AtValue::None)),
+                            IF!(!enabled => attrs!(At::Disabled => AtValue::None)),
+                            ev(Ev::Change, move |_| {
+                                let mut finals = finals;
+                                match id {
+                                    "individual" => finals.individual = !checked,
+                                    "team" => finals.team = !checked,
+                                    _ => finals.mixed_team = !checked,
+                                }
+                                Msg::ArcherMsg(index, ArcherMsg::FinalsChanged(finals))
+                            })
+                        ),
+                        label!(label, attrs!(At::For => format!("{}_final{}", id, index)))
+                    ]
+                })
+            ),
+        ]),
     ]
 }
 
+fn bow_type_name(bow_type: BowType) -> String {
+    match bow_type {
+        BowType::Recurve => t!("Recurve"),
+        BowType::Compound => t!("Compound"),
+        BowType::Barebow => t!("Barebow"),
+        BowType::Longbow => t!("Longbow"),
+        BowType::Traditional => t!("Traditional"),
+        BowType::Instinctive => t!("Instinctive"),
+    }
+}
+
+/// Explains why a class is offered or not, given the age reached within the season
+fn availability_note(availability: ClassAvailability, age: i32) -> String {
+    match availability {
+        ClassAvailability::InDefaultAgeRange => String::new(),
+        ClassAvailability::Upgrade => t!("Upgrade from regular class"),
+        ClassAvailability::TooYoung { min_age } => {
+            t!("Class too young", age = age, min_age = min_age)
+        }
+        ClassAvailability::TooOld { max_age } => {
+            t!("Class too old", age = age, max_age = max_age)
+        }
+    }
+}
+
+fn class_name(cls: &Class) -> String {
+    let locale = Locale::from_str(&rust_i18n::locale()).unwrap();
+    with_rules(|rules| {
+        rules
+            .class(cls)
+            .map_or(cls.to_string(), |cls| cls.name(locale).to_string())
+    })
+}
+
 pub fn update_archer(
     msg: ArcherMsg,
     index: usize,
@@ -362,9 +485,12 @@ pub fn update_archer(
             model.check_and_update_cls(index, orders);
         }
         ClassChanged(cls) => {
-            seed::log!("Selected cls", cls.map(|cls| cls.to_string()));
+            seed::log!("Selected cls", cls.as_ref().map(|cls| cls.to_string()));
             model.cls = cls;
             model.update_target_face();
+            if !model.may_shoot_finals() {
+                model.finals = Finals::default();
+            }
         }
         TargetFaceChanged(tf) => {
             seed::log!("Selected target", tf);
@@ -374,5 +500,13 @@ pub fn update_archer(
             seed::log!("Selected session", session);
             model.session = session;
         }
+        GenderChanged(gender) => {
+            seed::log!("Selected gender", gender);
+            model.gender = Some(gender);
+        }
+        FinalsChanged(finals) => {
+            seed::log!("Selected finals", finals);
+            model.finals = finals;
+        }
     }
 }
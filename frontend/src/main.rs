@@ -1,8 +1,15 @@
 mod archer;
 mod registrator;
+mod team;
 
 use archer::ArcherModel;
+use common::archer::{Finals, PastArcher};
+use common::class::Rules;
+use common::club::Club;
+use common::duplicate::{DuplicateReason, DuplicateWarning};
+use common::line_data::PrefillRequest;
 use common::locale::Locale;
+use common::tournament::Tournament;
 use email_address::EmailAddress;
 use rust_i18n::{i18n, t};
 use serde::{Deserialize, Serialize};
@@ -16,9 +23,19 @@ i18n!();
 struct Model {
     registrator: Registrator,
     archers: Vec<ArcherModel>,
+    #[serde(default)]
+    teams: Vec<team::TeamModel>,
 
     submitting: bool,
     locale: Locale,
+    /// Stamp of the backend when the form was opened, sent back with the registration
+    #[serde(skip)]
+    form_stamp: String,
+    #[serde(skip)]
+    club_suggestions: Vec<Club>,
+    /// Tournaments to choose from if the url doesn't name one
+    #[serde(skip)]
+    tournaments: Vec<Tournament>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -27,10 +44,27 @@ pub struct Registrator {
     mail: InsertedMail,
     comment: String,
     club: String,
+    /// Honeypot, never visible to humans
+    #[serde(default)]
+    website: String,
 }
 
 thread_local! {
     static BASE_URL: std::cell::RefCell<Url> = std::cell::RefCell::new(Url::new());
+    /// Slug of the tournament taken from the url `/t/{slug}`
+    static SLUG: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+    /// Class rules of the backend, the bundled ones until they are fetched
+    static RULES: std::cell::RefCell<Rules> = std::cell::RefCell::new(Rules::default());
+    /// Tournament settings of the backend, a season of the current year until they are fetched
+    static TOURNAMENT: std::cell::RefCell<Tournament> = std::cell::RefCell::new(Tournament::default());
+}
+
+pub fn with_rules<R>(f: impl FnOnce(&Rules) -> R) -> R {
+    RULES.with(|rules| f(&rules.borrow()))
+}
+
+pub fn tournament() -> Tournament {
+    TOURNAMENT.with(|tournament| tournament.borrow().clone())
 }
 
 impl Model {
@@ -41,14 +75,30 @@ impl Model {
                 mail: InsertedMail::Invalid(String::new()),
                 comment: String::new(),
                 club: String::new(),
+                website: String::new(),
             },
             archers: vec![ArcherModel::default()],
+            teams: Vec::new(),
             submitting: false,
             locale: Locale::De,
+            form_stamp: String::new(),
+            club_suggestions: Vec::new(),
+            tournaments: Vec::new(),
         }
     }
 }
 
+/// Url of the api of the tournament, e.g. `/api/t/{slug}/archers`
+fn tournament_api_url(path: &str) -> Url {
+    let slug = SLUG.with(|slug| slug.borrow().clone().unwrap_or_default());
+    BASE_URL.with(|base| base.borrow().clone().set_path(["api", "t", &slug, path]))
+}
+
+/// Key of the stored form, separate per tournament
+fn storage_key() -> String {
+    SLUG.with(|slug| format!("model-{}", slug.borrow().as_deref().unwrap_or_default()))
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 enum InsertedMail {
     Invalid(String),
@@ -80,19 +130,31 @@ fn init(url: Url, orders: &mut impl Orders<Msg>) -> Model {
     BASE_URL.with(|base_url| {
         *base_url.borrow_mut() = url.to_base_url();
     });
+    let slug = match url.path() {
+        [t, slug, ..] if t == "t" => Some(slug.clone()),
+        _ => None,
+    };
+    SLUG.with(|s| *s.borrow_mut() = slug.clone());
+    if slug.is_none() {
+        orders.perform_cmd(fetch_tournaments());
+        return Model::new();
+    }
+    orders.perform_cmd(fetch_rules());
+    orders.perform_cmd(fetch_tournament());
+    orders.perform_cmd(fetch_form_stamp());
+    if let Some(token) = url.search().get("prefill").and_then(|t| t.first()) {
+        orders.perform_cmd(fetch_prefill(token.clone()));
+    }
     let window = window();
     let Some(session_storage) = window.session_storage().ok().flatten() else {
         seed::log!("Couldn't load session storage");
         return Model::new();
     };
-    let model = if let Some(ser_model) = session_storage.get_item("model").unwrap() {
+    let model = if let Some(ser_model) = session_storage.get_item(&storage_key()).unwrap() {
         match serde_json::from_str::<Model>(&ser_model) {
             Ok(mut model) => {
                 model.submitting = false;
-                for (index, archer) in model.archers.iter_mut().enumerate() {
-                    archer.check_and_update_cls(index, orders);
-                    archer.update_target_face();
-                }
+                check_classes(&mut model.archers, orders);
                 model
             }
             Err(_) => {
@@ -114,15 +176,33 @@ pub enum Msg {
     ArcherMsg(usize, archer::ArcherMsg),
     AddArcher,
     RemoveArcher(usize),
+    TeamMsg(usize, team::TeamMsg),
+    AddTeam,
+    RemoveTeam(usize),
 
     NameChanged(String),
     ClubChanged(String),
+    /// Query and the clubs found for it
+    ClubsFetched(String, Vec<Club>),
+    RulesFetched(Rules),
+    TournamentFetched(Tournament),
+    TournamentsFetched(Vec<Tournament>),
+    FormStampFetched(String),
     MailChanged(String),
     CommentChanged(String),
+    WebsiteChanged(String),
 
     Submit,
+    SubmitAnyway,
     RegistrationFailed(String),
+    DuplicatesFound(Vec<DuplicateWarning>),
     RegistrationOk,
+    RegistrationPending,
+
+    RequestPrefill,
+    PrefillRequested,
+    PrefillFetched(Vec<PastArcher>),
+    PrefillFailed,
 
     ToggleLanguage,
 }
@@ -136,22 +216,24 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                 InsertedMail::Invalid(mail)
             }
         }
-        Msg::Submit => {
+        Msg::Submit | Msg::SubmitAnyway => {
             model.submitting = true;
             let mail = match &model.registrator.mail {
                 InsertedMail::Invalid(_) => unreachable!(),
                 InsertedMail::Valid(mail) => EmailAddress::from_str(mail).unwrap(),
             };
+            let rules = RULES.with(|rules| rules.borrow().clone());
+            // Archers without a name are not submitted, so the team members have to be renumbered
+            let submitted = submitted_archers(&model.archers);
             orders.perform_cmd(post_participants(common::line_data::CreateArchersPayload {
                 name: model.registrator.name.clone(),
                 mail: mail.clone(),
                 comment: model.registrator.comment.clone(),
                 club: model.registrator.club.clone(),
-                archers: model
-                    .archers
+                archers: submitted
                     .iter()
-                    .filter(|a| !a.first_name.is_empty() && !a.last_name.is_empty())
-                    .map(|a| {
+                    .map(|&index| {
+                        let a = &model.archers[index];
                         common::archer::Archer::new(
                             a.first_name.clone(),
                             a.last_name.clone(),
@@ -162,16 +244,41 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
                                     unreachable!("Submission only impossible if dob is valid")
                                 }
                             },
-                            a.cls.expect("Submission only possible if class is set"),
+                            a.cls
+                                .clone()
+                                .expect("Submission only possible if class is set"),
                             a.selected_target_face,
                             model.registrator.comment.clone(),
                             model.registrator.club.clone(),
                             a.session,
+                            a.gender.filter(|_| a.is_gender_neutral()),
+                            Finals {
+                                team: a.finals.team && team::in_team(&model.teams, index),
+                                ..a.finals
+                            },
+                            &rules,
+                            &tournament(),
                         )
                         .expect("It shouldn't be possible to produce invalid values")
                     })
                     .collect(),
+                teams: model
+                    .teams
+                    .iter()
+                    .map(|team| common::team::Team {
+                        name: team.name.trim().to_string(),
+                        members: team
+                            .members
+                            .iter()
+                            .flatten()
+                            .filter_map(|m| submitted.iter().position(|s| s == m))
+                            .collect(),
+                    })
+                    .collect(),
                 locale: model.locale,
+                ignore_duplicates: matches!(msg, Msg::SubmitAnyway),
+                website: model.registrator.website.clone(),
+                form_stamp: model.form_stamp.clone(),
             }));
         }
         Msg::RegistrationFailed(err) => {
@@ -181,17 +288,55 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
             seed::error!("Submission failed!", err);
             model.submitting = false;
         }
-        Msg::RegistrationOk => {
+        Msg::DuplicatesFound(duplicates) => {
+            model.submitting = false;
+            let submitted = submitted_archers(&model.archers);
+            let archers = duplicates
+                .iter()
+                .filter_map(|d| {
+                    let archer = &model.archers[*submitted.get(d.archer)?];
+                    Some(format!(
+                        "{} {} ({})",
+                        archer.first_name,
+                        archer.last_name,
+                        match d.reason {
+                            DuplicateReason::SameNameAndDateOfBirth => {
+                                t!("Same name and date of birth")
+                            }
+                            DuplicateReason::SimilarNameInClub => t!("Similar name in club"),
+                        }
+                    ))
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let register_anyway = seed::window()
+                .confirm_with_message(&format!(
+                    "{}\n{archers}\n\n{}",
+                    t!("Possible duplicates message"),
+                    t!("Register anyway question")
+                ))
+                .unwrap_or(false);
+            if register_anyway {
+                orders.send_msg(Msg::SubmitAnyway);
+            }
+        }
+        Msg::RegistrationOk | Msg::RegistrationPending => {
             seed::window()
-                .alert_with_message(&t!("Registration successful message"))
+                .alert_with_message(&if matches!(msg, Msg::RegistrationPending) {
+                    t!("Registration pending message")
+                } else {
+                    t!("Registration successful message")
+                })
                 .ok();
             seed::log!("Submission ok!");
             *model = Model {
                 registrator: model.registrator.clone(),
                 ..Model::new()
-            }
+            };
+            orders.perform_cmd(fetch_form_stamp());
         }
         Msg::CommentChanged(c) => model.registrator.comment = c,
+        Msg::WebsiteChanged(w) => model.registrator.website = w,
         Msg::ArcherMsg(index, a_msg) => {
             archer::update_archer(a_msg, index, &mut model.archers[index], orders)
         }
@@ -200,13 +345,70 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
         }
         Msg::RemoveArcher(index) => {
             model.archers.remove(index);
+            for team in model.teams.iter_mut() {
+                team.archer_removed(index);
+            }
+        }
+        Msg::TeamMsg(index, t_msg) => team::update_team(t_msg, &mut model.teams[index]),
+        Msg::AddTeam => {
+            model.teams.push(team::TeamModel::default());
+        }
+        Msg::RemoveTeam(index) => {
+            model.teams.remove(index);
         }
         Msg::NameChanged(name) => {
             model.registrator.name = name;
         }
         Msg::ClubChanged(club) => {
+            orders.perform_cmd(search_clubs(club.clone()));
             model.registrator.club = club;
         }
+        Msg::ClubsFetched(query, clubs) => {
+            // Answers to earlier queries may arrive late
+            if query == model.registrator.club {
+                model.club_suggestions = clubs;
+            }
+        }
+        Msg::RulesFetched(rules) => {
+            RULES.with(|r| *r.borrow_mut() = rules);
+            check_classes(&mut model.archers, orders);
+        }
+        Msg::TournamentFetched(tournament) => {
+            TOURNAMENT.with(|t| *t.borrow_mut() = tournament);
+            check_classes(&mut model.archers, orders);
+        }
+        Msg::TournamentsFetched(tournaments) => {
+            model.tournaments = tournaments;
+        }
+        Msg::FormStampFetched(stamp) => model.form_stamp = stamp,
+        Msg::RequestPrefill => {
+            if let InsertedMail::Valid(mail) = &model.registrator.mail {
+                orders.perform_cmd(request_prefill(PrefillRequest {
+                    mail: EmailAddress::from_str(mail).unwrap(),
+                    locale: model.locale,
+                }));
+            }
+        }
+        Msg::PrefillRequested => {
+            seed::window()
+                .alert_with_message(&t!("Prefill requested message"))
+                .ok();
+        }
+        Msg::PrefillFetched(archers) => {
+            if !archers.is_empty() {
+                model.archers = archers.into_iter().map(ArcherModel::from).collect();
+                // Members are indices of the replaced archers, so they are chosen again
+                for team in &mut model.teams {
+                    team.members.fill(None);
+                }
+                check_classes(&mut model.archers, orders);
+            }
+        }
+        Msg::PrefillFailed => {
+            seed::window()
+                .alert_with_message(&t!("Prefill failed message"))
+                .ok();
+        }
         Msg::ToggleLanguage => {
             model.locale = match model.locale {
                 Locale::En => Locale::De,
@@ -221,18 +423,69 @@ fn update(msg: Msg, model: &mut Model, orders: &mut impl Orders<Msg>) {
 
     if let Some(session_storage) = window().session_storage().ok().flatten() {
         session_storage
-            .set_item("model", &serde_json::to_string(&model).unwrap())
+            .set_item(&storage_key(), &serde_json::to_string(&model).unwrap())
             .unwrap()
     }
 }
 
+/// Indices of the archers which are submitted, the ones with a name
+fn submitted_archers(archers: &[ArcherModel]) -> Vec<usize> {
+    archers
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| !a.first_name.is_empty() && !a.last_name.is_empty())
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Selects valid classes again after the rules or the season changed
+fn check_classes(archers: &mut [ArcherModel], orders: &mut impl Orders<Msg>) {
+    for (index, archer) in archers.iter_mut().enumerate() {
+        archer.check_and_update_cls(index, orders);
+        archer.update_target_face();
+    }
+}
+
 fn view(model: &Model) -> Node<Msg> {
-    div![view_headline(), view_body(model), view_footer()]
+    if SLUG.with(|slug| slug.borrow().is_none()) {
+        return div![view_tournaments(model), view_footer()];
+    }
+    div![view_headline(model), view_body(model), view_footer()]
+}
+
+fn view_tournaments(model: &Model) -> Node<Msg> {
+    let now = chrono::Local::now().naive_local();
+    div![
+        h1!(t!("Tournaments")),
+        ul!(model
+            .tournaments
+            .iter()
+            .filter(|tournament| tournament.is_open(now))
+            .map(|tournament| li!(a!(
+                attrs!(At::Href => format!("/t/{}", tournament.slug)),
+                format!(
+                    "{} ({})",
+                    tournament.name,
+                    format_date(tournament.date, model.locale)
+                )
+            ))))
+    ]
 }
 
 fn view_body(model: &Model) -> Node<Msg> {
-    // let dob = model.date_of_birth;
-    // let bow_type = model.bow_type;
+    let tournament = tournament();
+    let closed = !tournament.is_open(chrono::Local::now().naive_local());
+    let offers_teams = with_rules(|rules| {
+        rules
+            .team_classes
+            .iter()
+            .any(|team| tournament.offers_team(team))
+    });
+    let teams_valid = team::members_unique(&model.teams)
+        && model
+            .teams
+            .iter()
+            .all(|team| team.team_class(&model.archers).is_ok());
     ul![
         C!("list"),
         li!(
@@ -245,28 +498,45 @@ fn view_body(model: &Model) -> Node<Msg> {
             ),
             input_ev(Ev::Click, |_| Msg::ToggleLanguage)
         ),
-        registrator::view_registrator(&model.registrator),
+        IF!(closed => li!(C!("closed"), strong!(t!("Registration closed")))),
+        registrator::view_registrator(&model.registrator, &model.club_suggestions),
         hr!(),
-        model
-            .archers
-            .iter()
-            .enumerate()
-            .map(|(index, archer)| { p!(li!(archer::archer_view(archer, index)), hr!()) }),
+        model.archers.iter().enumerate().map(|(index, archer)| {
+            p!(
+                li!(archer::archer_view(
+                    archer,
+                    index,
+                    team::in_team(&model.teams, index)
+                )),
+                hr!()
+            )
+        }),
         li!(button!(
             t!("Add archer"),
             input_ev(Ev::Click, |_| Msg::AddArcher)
         )),
+        IF!(offers_teams => hr!()),
+        IF!(offers_teams => li!(h2!(t!("Teams")))),
+        model
+            .teams
+            .iter()
+            .enumerate()
+            .map(|(index, team)| p!(li!(team::team_view(team, index, &model.archers)), hr!())),
+        IF!(offers_teams => li!(button!(
+            t!("Add team"),
+            input_ev(Ev::Click, |_| Msg::AddTeam)
+        ))),
         li!(br!()),
         li!(button!(
             t!("Submit"),
-            IF!(model.archers.is_empty() || model.archers.iter().any(|a| !a.ready_for_submission()) || model.registrator.club.is_empty() || !model.registrator.mail.is_valid() || model.submitting => attrs!(At::Disabled => AtValue::None)),
+            IF!(closed || !teams_valid || model.archers.is_empty() || model.archers.iter().any(|a| !a.ready_for_submission()) || model.registrator.club.is_empty() || !model.registrator.mail.is_valid() || model.submitting => attrs!(At::Disabled => AtValue::None)),
             input_ev(Ev::Click, |_| Msg::Submit)
         ))
     ]
 }
 
 async fn post_participants(data: common::line_data::CreateArchersPayload) -> Msg {
-    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "archers"]));
+    let url = tournament_api_url("archers");
     let request = Request::new(url.to_string())
         .method(Method::Post)
         .json(&data)
@@ -276,7 +546,17 @@ async fn post_participants(data: common::line_data::CreateArchersPayload) -> Msg
         Err(e) => return Msg::RegistrationFailed(format!("{e:?}")),
     };
     let text = response.text().await;
+    if response.status().code == 409 {
+        if let Some(duplicates) = text
+            .as_ref()
+            .ok()
+            .and_then(|t| serde_json::from_str(t).ok())
+        {
+            return Msg::DuplicatesFound(duplicates);
+        }
+    }
     match response.check_status() {
+        Ok(r) if r.status().code == 202 => Msg::RegistrationPending,
         Ok(_) => Msg::RegistrationOk,
         Err(e) => {
             seed::log!(e);
@@ -285,8 +565,86 @@ async fn post_participants(data: common::line_data::CreateArchersPayload) -> Msg
     }
 }
 
-fn view_headline() -> Vec<Node<Msg>> {
-    vec![h1!(t!("Headline")), h4!(t!("Headline date"))]
+async fn request_prefill(request: PrefillRequest) -> Msg {
+    let url = tournament_api_url("prefill");
+    let request = Request::new(url.to_string())
+        .method(Method::Post)
+        .json(&request)
+        .unwrap();
+    match fetch(request).await.and_then(|r| r.check_status()) {
+        Ok(_) => Msg::PrefillRequested,
+        Err(e) => Msg::RegistrationFailed(format!("{e:?}")),
+    }
+}
+
+async fn fetch_prefill(token: String) -> Msg {
+    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "prefill", &token]));
+    let response = match fetch(url.to_string()).await.and_then(|r| r.check_status()) {
+        Ok(response) => response,
+        Err(_) => return Msg::PrefillFailed,
+    };
+    match response.json().await {
+        Ok(archers) => Msg::PrefillFetched(archers),
+        Err(_) => Msg::PrefillFailed,
+    }
+}
+
+async fn search_clubs(query: String) -> Option<Msg> {
+    let url = BASE_URL.with(|base| {
+        base.borrow()
+            .clone()
+            .set_path(["api", "clubs"])
+            .set_search(UrlSearch::new(vec![("q", vec![query.clone()])]))
+    });
+    let response = fetch(url.to_string()).await.ok()?.check_status().ok()?;
+    Some(Msg::ClubsFetched(query, response.json().await.ok()?))
+}
+
+async fn fetch_rules() -> Option<Msg> {
+    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "rules"]));
+    let response = fetch(url.to_string()).await.ok()?.check_status().ok()?;
+    Some(Msg::RulesFetched(response.json().await.ok()?))
+}
+
+async fn fetch_form_stamp() -> Option<Msg> {
+    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "form-stamp"]));
+    let response = fetch(url.to_string()).await.ok()?.check_status().ok()?;
+    Some(Msg::FormStampFetched(response.json().await.ok()?))
+}
+
+async fn fetch_tournament() -> Option<Msg> {
+    let slug = SLUG.with(|slug| slug.borrow().clone())?;
+    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "t", &slug]));
+    let response = fetch(url.to_string()).await.ok()?.check_status().ok()?;
+    Some(Msg::TournamentFetched(response.json().await.ok()?))
+}
+
+async fn fetch_tournaments() -> Option<Msg> {
+    let url = BASE_URL.with(|base| base.borrow().clone().set_path(["api", "tournaments"]));
+    let response = fetch(url.to_string()).await.ok()?.check_status().ok()?;
+    Some(Msg::TournamentsFetched(response.json().await.ok()?))
+}
+
+fn format_date(date: chrono::NaiveDate, locale: Locale) -> String {
+    match locale {
+        Locale::En => date.format("%B %-d, %Y"),
+        Locale::De => date.format("%d.%m.%Y"),
+    }
+    .to_string()
+}
+
+fn view_headline(model: &Model) -> Vec<Node<Msg>> {
+    let tournament = tournament();
+    vec![
+        h1!(t!("Headline", name = tournament.name)),
+        h4!(
+            t!(
+                "Headline date",
+                date = format_date(tournament.date, model.locale)
+            ),
+            IF!(!tournament.venue.is_empty() => format!(" {}", t!("Headline venue", venue = tournament.venue)))
+        ),
+    ]
 }
 
 fn view_footer() -> Node<Msg> {
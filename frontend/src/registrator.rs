@@ -1,9 +1,11 @@
 use rust_i18n::t;
 use seed::{prelude::*, *};
 
+use common::club::Club;
+
 use crate::Msg;
 
-pub fn view_registrator(model: &crate::Registrator) -> Node<crate::Msg> {
+pub fn view_registrator(model: &crate::Registrator, club_suggestions: &[Club]) -> Node<crate::Msg> {
     ul![
         C!("list"),
         li!(h3![t!("Registrator")]),
@@ -16,13 +18,23 @@ pub fn view_registrator(model: &crate::Registrator) -> Node<crate::Msg> {
             input_ev(Ev::Input, Msg::NameChanged)
         )),
         li!(t!("Name of club")),
-        li!(input!(
-            attrs!(
-                At::Value => model.club
-                At::Style =>if model.club.is_empty() {"border: 1px solid red"} else {""}
+        li!(
+            input!(
+                attrs!(
+                    At::Value => model.club
+                    At::List => "clubs",
+                    At::AutoComplete => "off",
+                    At::Style =>if model.club.is_empty() {"border: 1px solid red"} else {""}
+                ),
+                input_ev(Ev::Input, Msg::ClubChanged)
             ),
-            input_ev(Ev::Input, Msg::ClubChanged)
-        )),
+            datalist!(
+                attrs!(At::Id => "clubs"),
+                club_suggestions
+                    .iter()
+                    .map(|club| option!(attrs!(At::Value => club.name)))
+            )
+        ),
         li!(t!("Mail address")),
         li!(input!(
             attrs!(
@@ -32,11 +44,29 @@ pub fn view_registrator(model: &crate::Registrator) -> Node<crate::Msg> {
             ),
             input_ev(Ev::Input, Msg::MailChanged)
         )),
+        li!(button!(
+            t!("Load archers from last time"),
+            IF!(!model.mail.is_valid() => attrs!(At::Disabled => AtValue::None)),
+            ev(Ev::Click, |_| Msg::RequestPrefill)
+        )),
         li!(t!("Comment")),
         li!(textarea!(
             attrs!(At::Value => model.comment),
             input_ev(Ev::Input, Msg::CommentChanged)
         )),
+        li!(
+            C!("hidden"),
+            attrs!(At::from("aria-hidden") => "true"),
+            input!(
+                attrs!(
+                    At::Name => "website",
+                    At::Value => model.website,
+                    At::TabIndex => -1,
+                    At::AutoComplete => "off",
+                ),
+                input_ev(Ev::Input, Msg::WebsiteChanged)
+            )
+        ),
         li!(br!()),
     ]
 }
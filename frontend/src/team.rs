@@ -0,0 +1,168 @@
+use common::{
+    locale::Locale,
+    team::{TeamError, TEAM_SIZE},
+};
+use rust_i18n::t;
+use seed::{prelude::*, *};
+use serde::{Deserialize, Serialize};
+
+use crate::{archer::ArcherModel, tournament, with_rules, Msg};
+
+#[derive(Serialize, Deserialize)]
+pub struct TeamModel {
+    pub name: String,
+    /// Indices of the archers in the form, `None` for slots not chosen yet
+    pub members: Vec<Option<usize>>,
+}
+
+impl Default for TeamModel {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            members: vec![None; TEAM_SIZE],
+        }
+    }
+}
+
+impl TeamModel {
+    /// Name of the team class the members form
+    pub fn team_class(&self, archers: &[ArcherModel]) -> Result<String, TeamError> {
+        if self.name.trim().is_empty() {
+            return Err(TeamError::MissingName);
+        }
+        let members = self
+            .members
+            .iter()
+            .map(|member| member.and_then(|m| archers.get(m)))
+            .collect::<Option<Vec<_>>>()
+            .filter(|members| members.iter().all(|a| !a.first_name.is_empty()))
+            .ok_or(TeamError::WrongSize)?;
+        if self
+            .members
+            .iter()
+            .enumerate()
+            .any(|(slot, m)| self.members[..slot].contains(m))
+        {
+            return Err(TeamError::DuplicateMember);
+        }
+        let classes = members
+            .iter()
+            .map(|a| a.cls.as_ref())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(TeamError::NoTeamClass)?;
+        let locale = Locale::from_str(&rust_i18n::locale()).unwrap();
+        with_rules(|rules| {
+            rules
+                .team_class_of(&classes, &tournament())
+                .map(|cls| cls.name(locale).to_string())
+        })
+    }
+
+    /// Keeps the members pointing to the same archers after an archer was removed from the form
+    pub fn archer_removed(&mut self, index: usize) {
+        for member in self.members.iter_mut() {
+            *member = match *member {
+                Some(m) if m == index => None,
+                Some(m) if m > index => Some(m - 1),
+                m => m,
+            };
+        }
+    }
+}
+
+/// Whether no archer is member of more than one team
+pub fn members_unique(teams: &[TeamModel]) -> bool {
+    let members: Vec<_> = teams
+        .iter()
+        .flat_map(|t| t.members.iter().flatten())
+        .collect();
+    members
+        .iter()
+        .enumerate()
+        .all(|(index, m)| !members[..index].contains(m))
+}
+
+/// Whether the archer at `index` in the form is member of a team
+pub fn in_team(teams: &[TeamModel], index: usize) -> bool {
+    teams.iter().any(|t| t.members.contains(&Some(index)))
+}
+
+pub enum TeamMsg {
+    NameChanged(String),
+    MemberChanged(usize, Option<usize>),
+}
+
+pub fn team_view(model: &TeamModel, index: usize, archers: &[ArcherModel]) -> Node<Msg> {
+    p![
+        C!("team"),
+        ul!(
+            C!("list flex"),
+            li!(
+                C!("horizontal"),
+                h3!(format!("{} {}:", t!("Team"), index + 1))
+            ),
+            li!(
+                C!("horizontal"),
+                button!(
+                    t!("Delete"),
+                    input_ev(Ev::Click, move |_| Msg::RemoveTeam(index))
+                )
+            )
+        ),
+        li!(t!("Team name")),
+        li!(input!(
+            attrs!(
+                At::Value => model.name,
+                At::Style =>if model.name.trim().is_empty() {"border: 1px solid red"} else {""}
+            ),
+            input_ev(Ev::Input, move |s| Msg::TeamMsg(
+                index,
+                TeamMsg::NameChanged(s)
+            ))
+        )),
+        li!(t!("Team members")),
+        model
+            .members
+            .iter()
+            .enumerate()
+            .map(|(slot, member)| li!(select!(
+                attrs!(At::Style => if member.is_none() {"border: 1px solid red"} else {""}),
+                option!(
+                    attrs!(At::Value => ""),
+                    IF!(member.is_none() => attrs!(At::Selected => AtValue::None)),
+                    "-"
+                ),
+                archers.iter().enumerate().map(|(a, archer)| option!(
+                    attrs!(At::Value => a),
+                    IF!(*member == Some(a) => attrs!(At::Selected => AtValue::None)),
+                    format!("{} {}", archer.first_name, archer.last_name)
+                )),
+                input_ev(Ev::Input, move |a| Msg::TeamMsg(
+                    index,
+                    TeamMsg::MemberChanged(slot, a.parse().ok())
+                ))
+            ))),
+        li!(match model.team_class(archers) {
+            Ok(cls) => format!("{} {}", t!("Team class"), cls),
+            Err(e) => team_error(e),
+        }),
+    ]
+}
+
+fn team_error(error: TeamError) -> String {
+    match error {
+        TeamError::MissingName | TeamError::WrongSize | TeamError::UnknownMember => {
+            t!("Team incomplete", size = TEAM_SIZE)
+        }
+        TeamError::DuplicateMember => t!("Team member twice"),
+        TeamError::DifferentBowTypes => t!("Team different bow types"),
+        TeamError::NoTeamClass => t!("No team class"),
+    }
+}
+
+pub fn update_team(msg: TeamMsg, model: &mut TeamModel) {
+    match msg {
+        TeamMsg::NameChanged(name) => model.name = name,
+        TeamMsg::MemberChanged(slot, member) => model.members[slot] = member,
+    }
+}
@@ -54,6 +54,12 @@ in
             sender_name = "Sender";
             sender_address = "me@mymail.com";
             subject = "Registration accepted";
+            reply_to = "Organizer <organizer@mymail.com>";
+            bcc = [ "Organizer <organizer@mymail.com>" ];
+          };
+          payment = {
+            iban = "DE00 0000 0000 0000 0000 00";
+            bic = "ABCDEFGHXXX";
           };
         }
       '';